@@ -0,0 +1,300 @@
+use crate::collectors::Metric;
+use clap::Args;
+use std::fmt;
+
+#[derive(Args)]
+pub struct CheckArgs {
+    /// Metric to check, as `NAME[{TAG=VALUE,...}][;WARNING[;CRITICAL]]`, e.g. `cpu_usage_iowait{core=cpu};20;50`.
+    /// Thresholds are Nagios ranges. May be given more than once.
+    #[arg(short, long = "metric", value_name = "SPEC", required = true)]
+    pub metrics: Vec<String>,
+
+    /// Seconds between the two samples, the window that rate metrics such as `cpu_usage_iowait` cover
+    #[arg(long, value_name = "SECONDS", default_value_t = 1)]
+    pub interval: u64,
+}
+
+/// Plugin states, numbered as the exit codes of the Nagios plugin API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok = 0,
+    Warning = 1,
+    Critical = 2,
+    Unknown = 3,
+}
+
+impl Status {
+    /// Rank for the overall state, CRITICAL > WARNING > UNKNOWN > OK. The exit codes cannot be
+    /// compared directly, as UNKNOWN (3) must not hide a CRITICAL result.
+    fn severity(self) -> u8 {
+        match self {
+            Status::Ok => 0,
+            Status::Unknown => 1,
+            Status::Warning => 2,
+            Status::Critical => 3,
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Status::Ok => "OK",
+            Status::Warning => "WARNING",
+            Status::Critical => "CRITICAL",
+            Status::Unknown => "UNKNOWN",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A Nagios threshold range. A value outside `start..=end` alerts, or inside it if `inside` is set.
+#[derive(Debug, Clone, PartialEq)]
+struct Range {
+    start: f64,
+    end: f64,
+    inside: bool,
+    text: String,
+}
+
+impl Range {
+    /// Parses `[@][START:][END]`, where an empty `END` is infinity and a `START` of `~` is negative infinity.
+    fn parse(text: &str) -> Result<Range, String> {
+        let invalid = || format!("invalid threshold range '{}'", text);
+        let (inside, range) = match text.strip_prefix('@') {
+            Some(range) => (true, range),
+            None => (false, text),
+        };
+        let (start, end) = match range.split_once(':') {
+            Some(("~", end)) => (f64::NEG_INFINITY, end),
+            Some((start, end)) => (start.parse().map_err(|_| invalid())?, end),
+            None => (0.0, range),
+        };
+        let end = match end {
+            "" if range.contains(':') => f64::INFINITY,
+            end => end.parse().map_err(|_| invalid())?,
+        };
+        if start > end {
+            return Err(invalid());
+        }
+        Ok(Range {
+            start,
+            end,
+            inside,
+            text: text.to_string(),
+        })
+    }
+
+    fn alerts(&self, value: f64) -> bool {
+        let within = self.start <= value && value <= self.end;
+        within == self.inside
+    }
+}
+
+/// One `--metric` argument: the series to check and its thresholds.
+#[derive(Debug, PartialEq)]
+struct Spec {
+    name: String,
+    tags: Vec<(String, String)>,
+    warning: Option<Range>,
+    critical: Option<Range>,
+}
+
+impl Spec {
+    fn parse(text: &str) -> Result<Spec, String> {
+        let mut parts = text.split(';');
+        let selector = parts.next().unwrap_or_default();
+        let range = |part: Option<&str>| part.filter(|p| !p.is_empty()).map(Range::parse).transpose();
+        let warning = range(parts.next())?;
+        let critical = range(parts.next())?;
+        if parts.next().is_some() {
+            return Err(format!("too many thresholds in '{}'", text));
+        }
+
+        let (name, tags) = match selector.split_once('{') {
+            Some((name, tags)) => {
+                let tags = tags.strip_suffix('}').ok_or_else(|| format!("unclosed tag filter in '{}'", text))?;
+                let tags = tags
+                    .split(',')
+                    .map(|tag| {
+                        tag.split_once('=')
+                            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+                            .ok_or_else(|| format!("invalid tag filter '{}' in '{}'", tag, text))
+                    })
+                    .collect::<Result<_, _>>()?;
+                (name, tags)
+            }
+            None => (selector, Vec::new()),
+        };
+        if name.is_empty() {
+            return Err(format!("missing metric name in '{}'", text));
+        }
+        Ok(Spec {
+            name: name.to_string(),
+            tags,
+            warning,
+            critical,
+        })
+    }
+
+    fn matches(&self, metric: &Metric) -> bool {
+        metric.name() == self.name && self.tags.iter().all(|tag| metric.tags.contains(tag))
+    }
+}
+
+/// Formats a perfdata label, e.g. `cpu_usage_iowait[cpu0]`, quoted if it contains a space, `=` or `'`.
+fn label(metric: &Metric) -> String {
+    let mut label = metric.name();
+    if !metric.tags.is_empty() {
+        let values: Vec<&str> = metric.tags.iter().map(|(_, v)| v.as_str()).collect();
+        label = format!("{}[{}]", label, values.join(","));
+    }
+    if label.contains([' ', '=', '\'']) {
+        label = format!("'{}'", label.replace('\'', "''"));
+    }
+    label
+}
+
+/// Evaluates the metric specs and returns the status and the plugin output line.
+///
+/// Every series matching a spec is checked on its own and the worst state wins. A spec that
+/// matches nothing or a non-numeric value makes the result UNKNOWN. The text lists the series in
+/// the resulting state, and the perfdata every checked series.
+pub fn evaluate(metrics: &[Metric], specs: &[String]) -> (Status, String) {
+    let specs = match specs.iter().map(|spec| Spec::parse(spec)).collect::<Result<Vec<_>, _>>() {
+        Ok(specs) => specs,
+        Err(e) => return (Status::Unknown, format!("RSYSMETRICS UNKNOWN - {}", e)),
+    };
+
+    let mut results = Vec::new();
+    let mut perfdata = Vec::new();
+    for spec in &specs {
+        let mut matched = false;
+        for metric in metrics.iter().filter(|metric| spec.matches(metric)) {
+            matched = true;
+            let Some(value) = metric.value.as_f64().filter(|v| v.is_finite()) else {
+                results.push((Status::Unknown, format!("{} is not numeric", label(metric))));
+                continue;
+            };
+            let status = if spec.critical.as_ref().is_some_and(|range| range.alerts(value)) {
+                Status::Critical
+            } else if spec.warning.as_ref().is_some_and(|range| range.alerts(value)) {
+                Status::Warning
+            } else {
+                Status::Ok
+            };
+            results.push((status, format!("{}={}", label(metric), value)));
+
+            let threshold = |range: &Option<Range>| range.as_ref().map(|r| r.text.clone()).unwrap_or_default();
+            let data = format!("{}={};{};{}", label(metric), value, threshold(&spec.warning), threshold(&spec.critical));
+            perfdata.push(data.trim_end_matches(';').to_string());
+        }
+        if !matched {
+            results.push((Status::Unknown, format!("no metric matches '{}'", spec.name)));
+        }
+    }
+
+    let status = results
+        .iter()
+        .map(|(status, _)| *status)
+        .max_by_key(|status| status.severity())
+        .unwrap_or(Status::Unknown);
+    let text: Vec<&str> = results
+        .iter()
+        .filter(|(result, _)| *result == status)
+        .map(|(_, text)| text.as_str())
+        .collect();
+    let mut output = format!("RSYSMETRICS {} - {}", status, text.join(", "));
+    if !perfdata.is_empty() {
+        output.push_str(" | ");
+        output.push_str(&perfdata.join(" "));
+    }
+    (status, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collectors::Value;
+
+    fn cpu(core: &str, value: f64) -> Metric {
+        Metric::new("cpu", "usage_iowait", Value::Float(value), vec![("core".to_string(), core.to_string())])
+    }
+
+    fn specs(specs: &[&str]) -> Vec<String> {
+        specs.iter().map(|spec| spec.to_string()).collect()
+    }
+
+    #[test]
+    fn test_range() {
+        let range = Range::parse("10").unwrap();
+        assert!(range.alerts(-1.0) && range.alerts(11.0) && !range.alerts(0.0) && !range.alerts(10.0));
+        let range = Range::parse("10:").unwrap();
+        assert!(range.alerts(9.0) && !range.alerts(1e9));
+        let range = Range::parse("~:10").unwrap();
+        assert!(!range.alerts(-1e9) && range.alerts(11.0));
+        let range = Range::parse("10:20").unwrap();
+        assert!(range.alerts(9.0) && !range.alerts(15.0) && range.alerts(21.0));
+        let range = Range::parse("@10:20").unwrap();
+        assert!(!range.alerts(9.0) && range.alerts(15.0) && !range.alerts(21.0));
+
+        assert!(Range::parse("20:10").is_err());
+        assert!(Range::parse("abc").is_err());
+        assert!(Range::parse("").is_err());
+    }
+
+    #[test]
+    fn test_spec_parse() {
+        let spec = Spec::parse("cpu_usage_iowait{core=cpu};;50").unwrap();
+        assert_eq!(spec.name, "cpu_usage_iowait");
+        assert_eq!(spec.tags, vec![("core".to_string(), "cpu".to_string())]);
+        assert_eq!(spec.warning, None);
+        assert_eq!(spec.critical, Some(Range::parse("50").unwrap()));
+
+        assert!(Spec::parse("memory_used_percent").unwrap().warning.is_none());
+        assert!(Spec::parse("cpu{core=cpu;1;2").is_err());
+        assert!(Spec::parse("cpu;1;2;3").is_err());
+        assert!(Spec::parse(";1;2").is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let metrics = vec![cpu("cpu0", 5.0), cpu("cpu1", 25.0), cpu("cpu", 15.0)];
+
+        let (status, output) = evaluate(&metrics, &specs(&["cpu_usage_iowait{core=cpu};20;50"]));
+        assert_eq!(status, Status::Ok);
+        assert_eq!(output, "RSYSMETRICS OK - cpu_usage_iowait[cpu]=15 | cpu_usage_iowait[cpu]=15;20;50");
+
+        let (status, output) = evaluate(&metrics, &specs(&["cpu_usage_iowait;20;50"]));
+        assert_eq!(status, Status::Warning);
+        assert_eq!(
+            output,
+            "RSYSMETRICS WARNING - cpu_usage_iowait[cpu1]=25 | cpu_usage_iowait[cpu0]=5;20;50 \
+             cpu_usage_iowait[cpu1]=25;20;50 cpu_usage_iowait[cpu]=15;20;50"
+        );
+
+        let (status, _) = evaluate(&metrics, &specs(&["cpu_usage_iowait{core=cpu1};10;20"]));
+        assert_eq!(status, Status::Critical);
+
+        // A missing metric is UNKNOWN, but does not hide a WARNING or CRITICAL result.
+        let (status, output) = evaluate(&metrics, &specs(&["cpu_usage_iowait;20;50", "memory_used_percent"]));
+        assert_eq!(status, Status::Warning);
+        assert!(output.starts_with("RSYSMETRICS WARNING - cpu_usage_iowait[cpu1]=25 | "));
+        let (status, _) = evaluate(&metrics, &specs(&["cpu_usage_iowait;10;20", "memory_used_percent"]));
+        assert_eq!(status, Status::Critical);
+
+        let (status, output) = evaluate(&metrics, &specs(&["cpu_usage_iowait;50", "memory_used_percent"]));
+        assert_eq!(status, Status::Unknown);
+        assert!(output.starts_with("RSYSMETRICS UNKNOWN - no metric matches 'memory_used_percent' | "));
+
+        let (status, output) = evaluate(&metrics, &specs(&["cpu_usage_iowait;abc"]));
+        assert_eq!(status, Status::Unknown);
+        assert_eq!(output, "RSYSMETRICS UNKNOWN - invalid threshold range 'abc'");
+    }
+
+    #[test]
+    fn test_label() {
+        let metric = Metric::new("temperature", "value", Value::Float(40.0), vec![("label".to_string(), "it's hot".to_string())]);
+        assert_eq!(label(&metric), "'temperature[it''s hot]'");
+    }
+}
@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use sysinfo::System;
 
-use super::{Collector, Metric};
+use super::{Collector, Metric, Value};
 
 #[cfg(target_os = "linux")]
 use super::linux;
@@ -34,11 +34,8 @@ impl Collector for CpuCollector {
 
         // Per-core usage (cross-platform)
         for cpu in self.system.cpus() {
-            metrics.push(Metric {
-                name: "cpu_usage".to_string(),
-                value: cpu.cpu_usage() as f64,
-                tags: vec![("core".to_string(), cpu.name().to_string())],
-            });
+            let tags = vec![("core".to_string(), cpu.name().to_string())];
+            metrics.push(Metric::new("cpu", "usage", Value::Float(cpu.cpu_usage() as f64), tags));
         }
 
         // Detailed CPU times (Linux-only)
@@ -48,14 +45,14 @@ impl Collector for CpuCollector {
                 for (cpu_name, times) in times_map {
                     let usage = linux::cpu::normalize(times);
                     let tags = vec![("core".to_string(), cpu_name)];
-                    metrics.push(Metric { name: "cpu_usage_user".to_string(), value: usage.user, tags: tags.clone() });
-                    metrics.push(Metric { name: "cpu_usage_system".to_string(), value: usage.system, tags: tags.clone() });
-                    metrics.push(Metric { name: "cpu_usage_idle".to_string(), value: usage.idle, tags: tags.clone() });
-                    metrics.push(Metric { name: "cpu_usage_iowait".to_string(), value: usage.iowait, tags: tags.clone() });
-                    metrics.push(Metric { name: "cpu_usage_irq".to_string(), value: usage.irq, tags: tags.clone() });
-                    metrics.push(Metric { name: "cpu_usage_softirq".to_string(), value: usage.softirq, tags: tags.clone() });
-                    metrics.push(Metric { name: "cpu_usage_steal".to_string(), value: usage.steal, tags: tags.clone() });
-                    metrics.push(Metric { name: "cpu_usage_guest".to_string(), value: usage.guest, tags: tags.clone() });
+                    metrics.push(Metric::new("cpu", "usage_user", Value::Float(usage.user), tags.clone()));
+                    metrics.push(Metric::new("cpu", "usage_system", Value::Float(usage.system), tags.clone()));
+                    metrics.push(Metric::new("cpu", "usage_idle", Value::Float(usage.idle), tags.clone()));
+                    metrics.push(Metric::new("cpu", "usage_iowait", Value::Float(usage.iowait), tags.clone()));
+                    metrics.push(Metric::new("cpu", "usage_irq", Value::Float(usage.irq), tags.clone()));
+                    metrics.push(Metric::new("cpu", "usage_softirq", Value::Float(usage.softirq), tags.clone()));
+                    metrics.push(Metric::new("cpu", "usage_steal", Value::Float(usage.steal), tags.clone()));
+                    metrics.push(Metric::new("cpu", "usage_guest", Value::Float(usage.guest), tags.clone()));
                 }
             }
         }
@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 
-use super::{Collector, Metric};
+use super::{Collector, Metric, MetricKind, Value};
 
 #[cfg(target_os = "linux")]
 use super::linux;
@@ -35,19 +35,19 @@ impl Collector for DiskCollector {
                         ("device".to_string(), disk_name),
                         ("disk_id".to_string(), io.disk_id),
                     ];
-                    metrics.push(Metric { name: "disk_read_bytes".to_string(), value: io.read_bytes as f64, tags: tags.clone() });
-                    metrics.push(Metric { name: "disk_written_bytes".to_string(), value: io.written_bytes as f64, tags: tags.clone() });
-                    metrics.push(Metric { name: "disk_reads".to_string(), value: io.reads as f64, tags: tags.clone() });
-                    metrics.push(Metric { name: "disk_writes".to_string(), value: io.writes as f64, tags: tags.clone() });
-                    metrics.push(Metric { name: "disk_read_time".to_string(), value: io.read_time as f64, tags: tags.clone() });
-                    metrics.push(Metric { name: "disk_write_time".to_string(), value: io.write_time as f64, tags: tags.clone() });
-                    metrics.push(Metric { name: "disk_io_in_progress".to_string(), value: io.io_in_progress as f64, tags: tags.clone() });
+                    metrics.push(Metric::new("disk", "read_bytes", Value::UInt(io.read_bytes), tags.clone()).with_kind(MetricKind::Counter));
+                    metrics.push(Metric::new("disk", "written_bytes", Value::UInt(io.written_bytes), tags.clone()).with_kind(MetricKind::Counter));
+                    metrics.push(Metric::new("disk", "reads", Value::UInt(io.reads), tags.clone()).with_kind(MetricKind::Counter));
+                    metrics.push(Metric::new("disk", "writes", Value::UInt(io.writes), tags.clone()).with_kind(MetricKind::Counter));
+                    metrics.push(Metric::new("disk", "read_time", Value::UInt(io.read_time), tags.clone()).with_kind(MetricKind::Counter));
+                    metrics.push(Metric::new("disk", "write_time", Value::UInt(io.write_time), tags.clone()).with_kind(MetricKind::Counter));
+                    metrics.push(Metric::new("disk", "io_in_progress", Value::UInt(io.io_in_progress), tags.clone()));
 
                     if let Some(temperatures) = io.temperature {
                         for (label, temp) in temperatures {
                             let mut temp_tags = tags.clone();
                             temp_tags.push(("label".to_string(), label));
-                            metrics.push(Metric { name: "disk_temperature".to_string(), value: temp, tags: temp_tags });
+                            metrics.push(Metric::new("disk", "temperature", Value::Float(temp), temp_tags));
                         }
                     }
                 }
@@ -1,6 +1,6 @@
 use std::fs;
 use std::path::{Path, PathBuf};
-use crate::collectors::Metric;
+use crate::collectors::{Metric, Value};
 
 fn read_metric(path: &Path) -> Option<f64> {
     fs::read_to_string(path).ok()?.trim().parse::<f64>().ok()
@@ -31,11 +31,7 @@ fn collect_hwmon_metrics(metrics: &mut Vec<Metric>, hwmon_path: &Path, tags: &[(
                             || "unknown".to_string(),
                             |s| s.trim().to_lowercase().replace(' ', "_"),
                         );
-                        metrics.push(Metric {
-                            name: format!("gpu_temperature_{}", label),
-                            value: value / 1000.0,
-                            tags: tags.to_owned(),
-                        });
+                        metrics.push(Metric::new("gpu", format!("temperature_{}", label), Value::Float(value / 1000.0), tags.to_owned()));
                     }
                 } else if file_name.starts_with("in") && file_name.ends_with("_input")
                     && let Some(value) = read_metric(&path) {
@@ -44,30 +40,18 @@ fn collect_hwmon_metrics(metrics: &mut Vec<Metric>, hwmon_path: &Path, tags: &[(
                             || "unknown".to_string(),
                             |s| s.trim().to_lowercase().replace(' ', "_"),
                         );
-                        metrics.push(Metric {
-                            name: format!("gpu_voltage_{}", label),
-                            value,
-                            tags: tags.to_owned(),
-                        });
+                        metrics.push(Metric::new("gpu", format!("voltage_{}", label), Value::Float(value), tags.to_owned()));
                     }
             }
         }
     }
 
     if let Some(value) = read_metric(&hwmon_path.join("power1_average")) {
-        metrics.push(Metric {
-            name: "gpu_power_average".to_string(),
-            value: value / 1_000_000.0,
-            tags: tags.to_owned(),
-        });
+        metrics.push(Metric::new("gpu", "power_average", Value::Float(value / 1_000_000.0), tags.to_owned()));
     }
 
     if let Some(value) = read_metric(&hwmon_path.join("fan1_input")) {
-        metrics.push(Metric {
-            name: "gpu_fan_speed".to_string(),
-            value,
-            tags: tags.to_owned(),
-        });
+        metrics.push(Metric::new("gpu", "fan_speed", Value::Float(value), tags.to_owned()));
     }
 }
 
@@ -89,59 +73,31 @@ pub async fn collect_gpu_metrics() -> Vec<Metric> {
                                 }
 
                                 if let Some(gpu_busy) = read_metric(&path.join("device/gpu_busy_percent")) {
-                                    metrics.push(Metric {
-                                        name: "gpu_usage".to_string(),
-                                        value: gpu_busy,
-                                        tags: tags.clone(),
-                                    });
+                                    metrics.push(Metric::new("gpu", "usage", Value::Float(gpu_busy), tags.clone()));
                                 }
 
                                 if let Some(sclk) = read_metric(&path.join("device/pp_dpm_sclk")) {
-                                    metrics.push(Metric {
-                                        name: "gpu_core_clock".to_string(),
-                                        value: sclk,
-                                        tags: tags.clone(),
-                                    });
+                                    metrics.push(Metric::new("gpu", "core_clock", Value::Float(sclk), tags.clone()));
                                 }
 
                                 if let Some(mclk) = read_metric(&path.join("device/pp_dpm_mclk")) {
-                                    metrics.push(Metric {
-                                        name: "gpu_memory_clock".to_string(),
-                                        value: mclk,
-                                        tags: tags.clone(),
-                                    });
+                                    metrics.push(Metric::new("gpu", "memory_clock", Value::Float(mclk), tags.clone()));
                                 }
 
                                 if let Some(vram_used) = read_metric(&path.join("device/mem_info_vram_used")) {
-                                    metrics.push(Metric {
-                                        name: "gpu_vram_used".to_string(),
-                                        value: vram_used,
-                                        tags: tags.clone(),
-                                    });
+                                    metrics.push(Metric::new("gpu", "vram_used", Value::Float(vram_used), tags.clone()));
                                 }
 
                                 if let Some(vram_total) = read_metric(&path.join("device/mem_info_vram_total")) {
-                                    metrics.push(Metric {
-                                        name: "gpu_vram_total".to_string(),
-                                        value: vram_total,
-                                        tags: tags.clone(),
-                                    });
+                                    metrics.push(Metric::new("gpu", "vram_total", Value::Float(vram_total), tags.clone()));
                                 }
 
                                 if let Some(gtt_used) = read_metric(&path.join("device/mem_info_gtt_used")) {
-                                    metrics.push(Metric {
-                                        name: "gpu_gtt_used".to_string(),
-                                        value: gtt_used,
-                                        tags: tags.clone(),
-                                    });
+                                    metrics.push(Metric::new("gpu", "gtt_used", Value::Float(gtt_used), tags.clone()));
                                 }
 
                                 if let Some(gtt_total) = read_metric(&path.join("device/mem_info_gtt_total")) {
-                                    metrics.push(Metric {
-                                        name: "gpu_gtt_total".to_string(),
-                                        value: gtt_total,
-                                        tags: tags.clone(),
-                                    });
+                                    metrics.push(Metric::new("gpu", "gtt_total", Value::Float(gtt_total), tags.clone()));
                                 }
                             }
                     }
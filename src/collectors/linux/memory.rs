@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use std::fs;
 use std::io::{self, BufRead, BufReader};
 
-use crate::collectors::{Collector, Metric};
+use crate::collectors::{Collector, Metric, Value};
 
 #[derive(Debug, Default)]
 pub struct LinuxMemoryCollector {
@@ -33,47 +33,47 @@ impl LinuxMemoryCollector {
 
     fn build_metrics(&self, meminfo: &HashMap<String, u64>) -> Vec<Metric> {
         let mut metrics = Vec::new();
-        let mem_total = meminfo.get("MemTotal").cloned().unwrap_or(0) as f64 * 1024.0;
-        let mem_free = meminfo.get("MemFree").cloned().unwrap_or(0) as f64 * 1024.0;
-        let mem_available = meminfo.get("MemAvailable").cloned().unwrap_or(0) as f64 * 1024.0;
-        let buffers = meminfo.get("Buffers").cloned().unwrap_or(0) as f64 * 1024.0;
-        let cached = meminfo.get("Cached").cloned().unwrap_or(0) as f64 * 1024.0;
-        let mem_used = mem_total - mem_available;
-
-        metrics.push(Metric { name: "memory_total".to_string(), value: mem_total, tags: vec![] });
-        metrics.push(Metric { name: "memory_used".to_string(), value: mem_used, tags: vec![] });
-        metrics.push(Metric { name: "memory_free".to_string(), value: mem_free, tags: vec![] });
-        metrics.push(Metric { name: "memory_available".to_string(), value: mem_available, tags: vec![] });
-        metrics.push(Metric { name: "memory_buffered".to_string(), value: buffers, tags: vec![] });
-        metrics.push(Metric { name: "memory_cached".to_string(), value: cached, tags: vec![] });
-
-        let swap_total = meminfo.get("SwapTotal").cloned().unwrap_or(0) as f64 * 1024.0;
-        let swap_free = meminfo.get("SwapFree").cloned().unwrap_or(0) as f64 * 1024.0;
-        let swap_used = swap_total - swap_free;
-        let swap_cached = meminfo.get("SwapCached").cloned().unwrap_or(0) as f64 * 1024.0;
-
-        metrics.push(Metric { name: "swap_total".to_string(), value: swap_total, tags: vec![] });
-        metrics.push(Metric { name: "swap_used".to_string(), value: swap_used, tags: vec![] });
-        metrics.push(Metric { name: "swap_free".to_string(), value: swap_free, tags: vec![] });
-        metrics.push(Metric { name: "swap_cached".to_string(), value: swap_cached, tags: vec![] });
-
-        let active = meminfo.get("Active").cloned().unwrap_or(0) as f64 * 1024.0;
-        let inactive = meminfo.get("Inactive").cloned().unwrap_or(0) as f64 * 1024.0;
-        let dirty = meminfo.get("Dirty").cloned().unwrap_or(0) as f64 * 1024.0;
-        let shmem = meminfo.get("Shmem").cloned().unwrap_or(0) as f64 * 1024.0;
-        let slab = meminfo.get("Slab").cloned().unwrap_or(0) as f64 * 1024.0;
-        let pagetables = meminfo.get("PageTables").cloned().unwrap_or(0) as f64 * 1024.0;
-        let zswap = meminfo.get("Zswap").cloned().unwrap_or(0) as f64 * 1024.0;
-        let zswapped = meminfo.get("Zswapped").cloned().unwrap_or(0) as f64 * 1024.0;
-
-        metrics.push(Metric { name: "memory_active".to_string(), value: active, tags: vec![] });
-        metrics.push(Metric { name: "memory_inactive".to_string(), value: inactive, tags: vec![] });
-        metrics.push(Metric { name: "memory_dirty".to_string(), value: dirty, tags: vec![] });
-        metrics.push(Metric { name: "memory_shmem".to_string(), value: shmem, tags: vec![] });
-        metrics.push(Metric { name: "memory_slab".to_string(), value: slab, tags: vec![] });
-        metrics.push(Metric { name: "memory_pagetables".to_string(), value: pagetables, tags: vec![] });
-        metrics.push(Metric { name: "zswap_size".to_string(), value: zswap, tags: vec![] });
-        metrics.push(Metric { name: "zswap_stored".to_string(), value: zswapped, tags: vec![] });
+        let mem_total = meminfo.get("MemTotal").cloned().unwrap_or(0) * 1024;
+        let mem_free = meminfo.get("MemFree").cloned().unwrap_or(0) * 1024;
+        let mem_available = meminfo.get("MemAvailable").cloned().unwrap_or(0) * 1024;
+        let buffers = meminfo.get("Buffers").cloned().unwrap_or(0) * 1024;
+        let cached = meminfo.get("Cached").cloned().unwrap_or(0) * 1024;
+        let mem_used = mem_total.saturating_sub(mem_available);
+
+        metrics.push(Metric::new("memory", "total", Value::UInt(mem_total), vec![]));
+        metrics.push(Metric::new("memory", "used", Value::UInt(mem_used), vec![]));
+        metrics.push(Metric::new("memory", "free", Value::UInt(mem_free), vec![]));
+        metrics.push(Metric::new("memory", "available", Value::UInt(mem_available), vec![]));
+        metrics.push(Metric::new("memory", "buffered", Value::UInt(buffers), vec![]));
+        metrics.push(Metric::new("memory", "cached", Value::UInt(cached), vec![]));
+
+        let swap_total = meminfo.get("SwapTotal").cloned().unwrap_or(0) * 1024;
+        let swap_free = meminfo.get("SwapFree").cloned().unwrap_or(0) * 1024;
+        let swap_used = swap_total.saturating_sub(swap_free);
+        let swap_cached = meminfo.get("SwapCached").cloned().unwrap_or(0) * 1024;
+
+        metrics.push(Metric::new("swap", "total", Value::UInt(swap_total), vec![]));
+        metrics.push(Metric::new("swap", "used", Value::UInt(swap_used), vec![]));
+        metrics.push(Metric::new("swap", "free", Value::UInt(swap_free), vec![]));
+        metrics.push(Metric::new("swap", "cached", Value::UInt(swap_cached), vec![]));
+
+        let active = meminfo.get("Active").cloned().unwrap_or(0) * 1024;
+        let inactive = meminfo.get("Inactive").cloned().unwrap_or(0) * 1024;
+        let dirty = meminfo.get("Dirty").cloned().unwrap_or(0) * 1024;
+        let shmem = meminfo.get("Shmem").cloned().unwrap_or(0) * 1024;
+        let slab = meminfo.get("Slab").cloned().unwrap_or(0) * 1024;
+        let pagetables = meminfo.get("PageTables").cloned().unwrap_or(0) * 1024;
+        let zswap = meminfo.get("Zswap").cloned().unwrap_or(0) * 1024;
+        let zswapped = meminfo.get("Zswapped").cloned().unwrap_or(0) * 1024;
+
+        metrics.push(Metric::new("memory", "active", Value::UInt(active), vec![]));
+        metrics.push(Metric::new("memory", "inactive", Value::UInt(inactive), vec![]));
+        metrics.push(Metric::new("memory", "dirty", Value::UInt(dirty), vec![]));
+        metrics.push(Metric::new("memory", "shmem", Value::UInt(shmem), vec![]));
+        metrics.push(Metric::new("memory", "slab", Value::UInt(slab), vec![]));
+        metrics.push(Metric::new("memory", "pagetables", Value::UInt(pagetables), vec![]));
+        metrics.push(Metric::new("zswap", "size", Value::UInt(zswap), vec![]));
+        metrics.push(Metric::new("zswap", "stored", Value::UInt(zswapped), vec![]));
 
         metrics
     }
@@ -194,52 +194,52 @@ DirectMap1G:    19922944 kB
 
         assert_eq!(metrics.len(), 18);
 
-        let memory_total = metrics.iter().find(|m| m.name == "memory_total").unwrap();
-        assert_eq!(memory_total.value, 32499764.0 * 1024.0);
+        let memory_total = metrics.iter().find(|m| m.name() == "memory_total").unwrap();
+        assert_eq!(memory_total.value, Value::UInt(32499764 * 1024));
 
-        let memory_used = metrics.iter().find(|m| m.name == "memory_used").unwrap();
-        assert_eq!(memory_used.value, (32499764.0 - 27735004.0) * 1024.0);
+        let memory_used = metrics.iter().find(|m| m.name() == "memory_used").unwrap();
+        assert_eq!(memory_used.value, Value::UInt((32499764 - 27735004) * 1024));
         
-        let memory_available = metrics.iter().find(|m| m.name == "memory_available").unwrap();
-        assert_eq!(memory_available.value, 27735004.0 * 1024.0);
+        let memory_available = metrics.iter().find(|m| m.name() == "memory_available").unwrap();
+        assert_eq!(memory_available.value, Value::UInt(27735004 * 1024));
 
-        let memory_buffered = metrics.iter().find(|m| m.name == "memory_buffered").unwrap();
-        assert_eq!(memory_buffered.value, 2672.0 * 1024.0);
+        let memory_buffered = metrics.iter().find(|m| m.name() == "memory_buffered").unwrap();
+        assert_eq!(memory_buffered.value, Value::UInt(2672 * 1024));
 
-        let memory_cached = metrics.iter().find(|m| m.name == "memory_cached").unwrap();
-        assert_eq!(memory_cached.value, 6205420.0 * 1024.0);
+        let memory_cached = metrics.iter().find(|m| m.name() == "memory_cached").unwrap();
+        assert_eq!(memory_cached.value, Value::UInt(6205420 * 1024));
 
-        let swap_total = metrics.iter().find(|m| m.name == "swap_total").unwrap();
-        assert_eq!(swap_total.value, 25165820.0 * 1024.0);
+        let swap_total = metrics.iter().find(|m| m.name() == "swap_total").unwrap();
+        assert_eq!(swap_total.value, Value::UInt(25165820 * 1024));
 
-        let swap_used = metrics.iter().find(|m| m.name == "swap_used").unwrap();
-        assert_eq!(swap_used.value, (25165820.0 - 25165820.0) * 1024.0);
+        let swap_used = metrics.iter().find(|m| m.name() == "swap_used").unwrap();
+        assert_eq!(swap_used.value, Value::UInt(0));
 
-        let swap_cached = metrics.iter().find(|m| m.name == "swap_cached").unwrap();
-        assert_eq!(swap_cached.value, 0.0);
+        let swap_cached = metrics.iter().find(|m| m.name() == "swap_cached").unwrap();
+        assert_eq!(swap_cached.value, Value::UInt(0));
 
-        let memory_active = metrics.iter().find(|m| m.name == "memory_active").unwrap();
-        assert_eq!(memory_active.value, 6685232.0 * 1024.0);
+        let memory_active = metrics.iter().find(|m| m.name() == "memory_active").unwrap();
+        assert_eq!(memory_active.value, Value::UInt(6685232 * 1024));
 
-        let memory_inactive = metrics.iter().find(|m| m.name == "memory_inactive").unwrap();
-        assert_eq!(memory_inactive.value, 3552724.0 * 1024.0);
+        let memory_inactive = metrics.iter().find(|m| m.name() == "memory_inactive").unwrap();
+        assert_eq!(memory_inactive.value, Value::UInt(3552724 * 1024));
 
-        let memory_dirty = metrics.iter().find(|m| m.name == "memory_dirty").unwrap();
-        assert_eq!(memory_dirty.value, 2208.0 * 1024.0);
+        let memory_dirty = metrics.iter().find(|m| m.name() == "memory_dirty").unwrap();
+        assert_eq!(memory_dirty.value, Value::UInt(2208 * 1024));
 
-        let memory_shmem = metrics.iter().find(|m| m.name == "memory_shmem").unwrap();
-        assert_eq!(memory_shmem.value, 86232.0 * 1024.0);
+        let memory_shmem = metrics.iter().find(|m| m.name() == "memory_shmem").unwrap();
+        assert_eq!(memory_shmem.value, Value::UInt(86232 * 1024));
 
-        let memory_slab = metrics.iter().find(|m| m.name == "memory_slab").unwrap();
-        assert_eq!(memory_slab.value, 406816.0 * 1024.0);
+        let memory_slab = metrics.iter().find(|m| m.name() == "memory_slab").unwrap();
+        assert_eq!(memory_slab.value, Value::UInt(406816 * 1024));
 
-        let memory_pagetables = metrics.iter().find(|m| m.name == "memory_pagetables").unwrap();
-        assert_eq!(memory_pagetables.value, 56652.0 * 1024.0);
+        let memory_pagetables = metrics.iter().find(|m| m.name() == "memory_pagetables").unwrap();
+        assert_eq!(memory_pagetables.value, Value::UInt(56652 * 1024));
 
-        let zswap = metrics.iter().find(|m| m.name == "zswap_size").unwrap();
-        assert_eq!(zswap.value, 0.0 * 1024.0);
+        let zswap = metrics.iter().find(|m| m.name() == "zswap_size").unwrap();
+        assert_eq!(zswap.value, Value::UInt(0));
 
-        let zswapped = metrics.iter().find(|m| m.name == "zswap_stored").unwrap();
-        assert_eq!(zswapped.value, 0.0 * 1024.0);
+        let zswapped = metrics.iter().find(|m| m.name() == "zswap_stored").unwrap();
+        assert_eq!(zswapped.value, Value::UInt(0));
     }
 }
\ No newline at end of file
@@ -3,6 +3,8 @@ use async_trait::async_trait;
 use sysinfo::System;
 
 use super::{Collector, Metric};
+#[cfg(not(target_os = "linux"))]
+use super::Value;
 
 #[cfg(target_os = "linux")]
 use super::linux;
@@ -42,47 +44,13 @@ impl Collector for MemoryCollector {
             self.system.refresh_memory();
             let mut metrics = Vec::new();
 
-            metrics.push(Metric {
-                name: "memory_total".to_string(),
-                value: self.system.total_memory() as f64,
-                tags: vec![],
-            });
-
-            metrics.push(Metric {
-                name: "memory_used".to_string(),
-                value: self.system.used_memory() as f64,
-                tags: vec![],
-            });
-
-            metrics.push(Metric {
-                name: "memory_available".to_string(),
-                value: self.system.available_memory() as f64,
-                tags: vec![],
-            });
-
-            metrics.push(Metric {
-                name: "memory_free".to_string(),
-                value: self.system.free_memory() as f64,
-                tags: vec![],
-            });
-
-            metrics.push(Metric {
-                name: "swap_total".to_string(),
-                value: self.system.total_swap() as f64,
-                tags: vec![],
-            });
-
-            metrics.push(Metric {
-                name: "swap_used".to_string(),
-                value: self.system.used_swap() as f64,
-                tags: vec![],
-            });
-
-            metrics.push(Metric {
-                name: "swap_free".to_string(),
-                value: self.system.free_swap() as f64,
-                tags: vec![],
-            });
+            metrics.push(Metric::new("memory", "total", Value::UInt(self.system.total_memory()), vec![]));
+            metrics.push(Metric::new("memory", "used", Value::UInt(self.system.used_memory()), vec![]));
+            metrics.push(Metric::new("memory", "available", Value::UInt(self.system.available_memory()), vec![]));
+            metrics.push(Metric::new("memory", "free", Value::UInt(self.system.free_memory()), vec![]));
+            metrics.push(Metric::new("swap", "total", Value::UInt(self.system.total_swap()), vec![]));
+            metrics.push(Metric::new("swap", "used", Value::UInt(self.system.used_swap()), vec![]));
+            metrics.push(Metric::new("swap", "free", Value::UInt(self.system.free_swap()), vec![]));
 
             metrics
         }
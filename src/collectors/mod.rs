@@ -10,13 +10,82 @@ pub mod gpu;
 pub mod temperature;
 
 use async_trait::async_trait;
+use std::time::SystemTime;
+
+// The value of a metric point
+#[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code)]
+pub enum Value {
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+}
+
+impl Value {
+    /// Returns the value as a number, or `None` for strings. Booleans map to 0 and 1.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Int(v) => Some(*v as f64),
+            Value::UInt(v) => Some(*v as f64),
+            Value::Float(v) => Some(*v),
+            Value::Bool(v) => Some(if *v { 1.0 } else { 0.0 }),
+            Value::String(_) => None,
+        }
+    }
+}
+
+// Whether a metric is a point-in-time measurement or a monotonically increasing total
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MetricKind {
+    #[default]
+    Gauge,
+    Counter,
+}
 
 // A generic metric point
-#[derive(Debug)]
+//
+// Each collector chooses the measurement and field explicitly, which gives exporters
+// a stable schema independent of how the combined name is spelled.
+#[derive(Debug, Clone)]
 pub struct Metric {
-    pub name: String,
-    pub value: f64,
+    pub measurement: String,
+    pub field: String,
+    pub value: Value,
     pub tags: Vec<(String, String)>,
+    pub kind: MetricKind,
+    // When the value was sampled. Collectors may set it themselves; otherwise the main
+    // loop fills in the time at which the collector was invoked.
+    pub timestamp: Option<SystemTime>,
+}
+
+impl Metric {
+    pub fn new(measurement: &str, field: impl Into<String>, value: Value, tags: Vec<(String, String)>) -> Self {
+        Metric {
+            measurement: measurement.to_string(),
+            field: field.into(),
+            value,
+            tags,
+            kind: MetricKind::Gauge,
+            timestamp: None,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: MetricKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Returns the flat metric name, `<measurement>_<field>`, used by exporters without a field concept.
+    ///
+    /// A field named `value` is the only field of its measurement and is left out, e.g. `temperature`.
+    pub fn name(&self) -> String {
+        if self.field == "value" {
+            return self.measurement.clone();
+        }
+        format!("{}_{}", self.measurement, self.field)
+    }
 }
 
 #[async_trait]
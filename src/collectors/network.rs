@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use sysinfo::{Networks};
 
-use super::{Collector, Metric};
+use super::{Collector, Metric, MetricKind, Value};
 
 #[cfg(target_os = "linux")]
 use std::sync::LazyLock;
@@ -41,26 +41,10 @@ impl Collector for NetworkCollector {
                 continue;
             }
             let tags = vec![("interface".to_string(), interface_name.to_string())];
-            metrics.push(Metric {
-                name: "network_received".to_string(),
-                value: data.total_received() as f64,
-                tags: tags.clone(),
-            });
-            metrics.push(Metric {
-                name: "network_transmitted".to_string(),
-                value: data.total_transmitted() as f64,
-                tags: tags.clone(),
-            });
-            metrics.push(Metric {
-                name: "network_packets_received".to_string(),
-                value: data.packets_received() as f64,
-                tags: tags.clone(),
-            });
-            metrics.push(Metric {
-                name: "network_packets_transmitted".to_string(),
-                value: data.packets_transmitted() as f64,
-                tags: tags.clone(),
-            });
+            metrics.push(Metric::new("network", "received", Value::UInt(data.total_received()), tags.clone()).with_kind(MetricKind::Counter));
+            metrics.push(Metric::new("network", "transmitted", Value::UInt(data.total_transmitted()), tags.clone()).with_kind(MetricKind::Counter));
+            metrics.push(Metric::new("network", "packets_received", Value::UInt(data.total_packets_received()), tags.clone()).with_kind(MetricKind::Counter));
+            metrics.push(Metric::new("network", "packets_transmitted", Value::UInt(data.total_packets_transmitted()), tags.clone()).with_kind(MetricKind::Counter));
         }
 
         metrics
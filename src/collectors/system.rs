@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use sysinfo::System;
 
-use super::{Collector, Metric};
+use super::{Collector, Metric, Value};
 
 pub struct SystemCollector;
 
@@ -22,29 +22,13 @@ impl Collector for SystemCollector {
 
         // Uptime
         let uptime = System::uptime();
-        metrics.push(Metric {
-            name: "system_uptime".to_string(),
-            value: uptime as f64,
-            tags: vec![],
-        });
+        metrics.push(Metric::new("system", "uptime", Value::UInt(uptime), vec![]));
 
         // Load average
         let load_avg = System::load_average();
-        metrics.push(Metric {
-            name: "system_load_average_1m".to_string(),
-            value: load_avg.one,
-            tags: vec![],
-        });
-        metrics.push(Metric {
-            name: "system_load_average_5m".to_string(),
-            value: load_avg.five,
-            tags: vec![],
-        });
-        metrics.push(Metric {
-            name: "system_load_average_15m".to_string(),
-            value: load_avg.fifteen,
-            tags: vec![],
-        });
+        metrics.push(Metric::new("system", "load_average_1m", Value::Float(load_avg.one), vec![]));
+        metrics.push(Metric::new("system", "load_average_5m", Value::Float(load_avg.five), vec![]));
+        metrics.push(Metric::new("system", "load_average_15m", Value::Float(load_avg.fifteen), vec![]));
 
         metrics
     }
@@ -4,7 +4,7 @@ use sysinfo::Components;
 
 use crate::config::TemperatureCollectorConfig;
 
-use super::{Collector, Metric};
+use super::{Collector, Metric, Value};
 
 pub struct TemperatureCollector {
     components: Components,
@@ -78,11 +78,7 @@ impl Collector for TemperatureCollector {
 
             if let Some(temperature) = component.temperature() {
                 let tags = vec![("label".to_string(), component.label().to_string())];
-                metrics.push(Metric {
-                    name: "temperature".to_string(),
-                    value: temperature as f64,
-                    tags,
-                });
+                metrics.push(Metric::new("temperature", "value", Value::Float(temperature as f64), tags));
             }
         }
 
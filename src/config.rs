@@ -1,14 +1,25 @@
 use serde::Deserialize;
+use std::collections::BTreeMap;
 
 #[derive(Deserialize, Debug)]
 pub struct Config {
     #[serde(default = "default_collect_interval")]
     pub collect_interval: u64,
-    pub exporter: Exporter,
+    /// A single exporter, kept for configurations written before `[[exporters]]` existed.
+    pub exporter: Option<Exporter>,
+    #[serde(default)]
+    pub exporters: Vec<Exporter>,
     #[serde(default)]
     pub collectors: Collectors,
 }
 
+impl Config {
+    /// Returns every configured exporter, the legacy `[exporter]` table first.
+    pub fn exporters(&self) -> impl Iterator<Item = &Exporter> {
+        self.exporter.iter().chain(self.exporters.iter())
+    }
+}
+
 #[derive(Deserialize, Debug, Default)]
 pub struct Collectors {
     #[serde(default = "default_true")]
@@ -41,13 +52,42 @@ fn default_true() -> bool {
     true
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum Exporter {
     #[serde(rename = "influxdb")]
     InfluxDB(InfluxDBConfig),
+    #[serde(rename = "prometheus")]
+    Prometheus(PrometheusConfig),
+    #[serde(rename = "otlp")]
+    Otlp(OtlpConfig),
+    #[serde(rename = "remote_write")]
+    RemoteWrite(RemoteWriteConfig),
+    #[serde(rename = "graphite")]
+    Graphite(GraphiteConfig),
+    #[serde(rename = "statsd")]
+    Statsd(StatsdConfig),
+    #[serde(rename = "jsonl")]
+    JsonLines(JsonLinesConfig),
+    #[serde(rename = "csv")]
+    Csv(CsvConfig),
+    #[serde(rename = "mqtt")]
+    Mqtt(MqttConfig),
+    #[serde(rename = "sqlite")]
+    Sqlite(SqliteConfig),
+    #[serde(rename = "parquet")]
+    Parquet(ParquetConfig),
+    #[serde(rename = "webhook")]
+    Webhook(WebhookConfig),
+    #[serde(rename = "elasticsearch")]
+    Elasticsearch(ElasticsearchConfig),
+    #[serde(rename = "collectd")]
+    Collectd(CollectdConfig),
+    #[serde(rename = "zabbix")]
+    Zabbix(ZabbixConfig),
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct InfluxDBConfig {
     pub url: String,
     // V2 fields
@@ -58,8 +98,483 @@ pub struct InfluxDBConfig {
     pub db: Option<String>,
     pub username: Option<String>,
     pub password: Option<String>,
+    /// Keeps failed batches on disk and replays them once the endpoint is reachable again.
+    pub spool: Option<SpoolConfig>,
+    /// Request timeout in seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Compresses request bodies with gzip.
+    #[serde(default)]
+    pub gzip: bool,
+    /// Bodies smaller than this many bytes are sent uncompressed.
+    #[serde(default = "default_gzip_min_size")]
+    pub gzip_min_size: usize,
+    /// Maximum number of lines sent in one request.
+    #[serde(default = "default_max_batch_lines")]
+    pub max_batch_lines: usize,
+    /// Maximum size of one request body in bytes, before compression.
+    #[serde(default = "default_max_batch_bytes")]
+    pub max_batch_bytes: usize,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub precision: Precision,
+}
+
+/// Timestamp precision of the written points.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    #[default]
+    S,
+    Ms,
+    Us,
+    Ns,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RetryConfig {
+    /// Number of retries after the first attempt. Only transient failures are retried.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Delay before the first retry in milliseconds, doubled after every attempt.
+    #[serde(default = "default_initial_backoff")]
+    pub initial_backoff: u64,
+    /// Upper bound for the delay between retries in milliseconds.
+    #[serde(default = "default_max_backoff")]
+    pub max_backoff: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_retries: default_max_retries(),
+            initial_backoff: default_initial_backoff(),
+            max_backoff: default_max_backoff(),
+        }
+    }
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+
+fn default_gzip_min_size() -> usize {
+    1024
+}
+
+fn default_max_batch_lines() -> usize {
+    5000
+}
+
+fn default_max_batch_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_initial_backoff() -> u64 {
+    500
+}
+
+fn default_max_backoff() -> u64 {
+    10_000
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SpoolConfig {
+    pub dir: String,
+    /// Maximum total size of spooled batches in bytes. The oldest batches are dropped first.
+    #[serde(default = "default_spool_max_size")]
+    pub max_size: u64,
+    /// Maximum age of a spooled batch in seconds.
+    #[serde(default = "default_spool_max_age")]
+    pub max_age: u64,
+}
+
+fn default_spool_max_size() -> u64 {
+    100 * 1024 * 1024
+}
+
+fn default_spool_max_age() -> u64 {
+    24 * 60 * 60
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PrometheusConfig {
+    #[serde(default = "default_prometheus_listen")]
+    pub listen: String,
+    #[serde(default = "default_prometheus_path")]
+    pub path: String,
+}
+
+fn default_prometheus_listen() -> String {
+    "0.0.0.0:9101".to_string()
+}
+
+fn default_prometheus_path() -> String {
+    "/metrics".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct OtlpConfig {
+    #[serde(default = "default_otlp_endpoint")]
+    pub endpoint: String,
+    /// Extra request headers, e.g. for authentication.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Resource attributes sent next to `host.name`, e.g. `service.name`.
+    #[serde(default)]
+    pub resource_attributes: BTreeMap<String, String>,
+    /// Request timeout in seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+fn default_otlp_endpoint() -> String {
+    "http://localhost:4318/v1/metrics".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RemoteWriteConfig {
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Extra request headers, e.g. `X-Scope-OrgID` for multi-tenant receivers.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Request timeout in seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GraphiteConfig {
+    /// Address of the Carbon plaintext listener.
+    #[serde(default = "default_graphite_address")]
+    pub address: String,
+    /// Path template, see the example configuration for the placeholders.
+    #[serde(default = "default_graphite_template")]
+    pub template: String,
+    /// Tags whose values replace `{tags}`, in this order. All tags are used if unset.
+    pub tags: Option<Vec<String>>,
+    /// Connect timeout in seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+fn default_graphite_address() -> String {
+    "localhost:2003".to_string()
+}
+
+fn default_graphite_template() -> String {
+    "{host}.{measurement}.{tags}.{field}".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct StatsdConfig {
+    #[serde(default = "default_statsd_address")]
+    pub address: String,
+    /// Prepended to every metric name, separated by a dot.
+    pub prefix: Option<String>,
+    /// Sends tags in the DogStatsD `|#k:v` syntax instead of appending their values to the name.
+    #[serde(default)]
+    pub dogstatsd: bool,
+    /// Maximum payload size of one UDP packet in bytes.
+    #[serde(default = "default_statsd_mtu")]
+    pub mtu: usize,
+}
+
+fn default_statsd_address() -> String {
+    "127.0.0.1:8125".to_string()
+}
+
+fn default_statsd_mtu() -> usize {
+    1432
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct JsonLinesConfig {
+    /// Output file. Metrics are written to stdout if unset.
+    pub path: Option<String>,
+    #[serde(default)]
+    pub mode: JsonLinesMode,
+    pub rotate: Option<RotateConfig>,
+}
+
+/// Whether a JSON object is written for every metric or for every collection cycle.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonLinesMode {
+    #[default]
+    Metric,
+    Cycle,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CsvConfig {
+    pub path: String,
+    pub rotate: Option<RotateConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RotateConfig {
+    /// Rotates the file before it grows beyond this many bytes.
+    pub max_size: Option<u64>,
+    /// Rotates the file once it is this many seconds old.
+    pub max_age: Option<u64>,
+    /// Number of rotated files to keep.
+    #[serde(default = "default_rotate_max_files")]
+    pub max_files: usize,
+}
+
+fn default_rotate_max_files() -> usize {
+    5
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MqttConfig {
+    #[serde(default = "default_mqtt_host")]
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    /// Defaults to `rsysmetrics-<hostname>`.
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub tls: Option<MqttTlsConfig>,
+    /// Topic template, see the example configuration for the placeholders.
+    #[serde(default = "default_mqtt_topic")]
+    pub topic: String,
+    #[serde(default)]
+    pub qos: u8,
+    #[serde(default)]
+    pub retain: bool,
+    /// Announces every series as a sensor through Home Assistant MQTT discovery.
+    #[serde(default)]
+    pub homeassistant: bool,
+    #[serde(default = "default_mqtt_discovery_prefix")]
+    pub discovery_prefix: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct MqttTlsConfig {
+    /// PEM file with the CA certificates to trust. The system roots are used if unset.
+    pub ca_file: Option<String>,
+    /// PEM files with the client certificate and key, for brokers that require client authentication.
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+}
+
+fn default_mqtt_host() -> String {
+    "localhost".to_string()
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_topic() -> String {
+    "rsysmetrics/{host}/{name}/{tags}".to_string()
+}
+
+fn default_mqtt_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SqliteConfig {
+    pub path: String,
+    /// Samples older than this many seconds are deleted.
+    #[serde(default = "default_sqlite_retention")]
+    pub retention: u64,
+}
+
+fn default_sqlite_retention() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ParquetConfig {
+    /// Directory for the hourly files.
+    pub dir: String,
+    #[serde(default)]
+    pub schema: ParquetSchema,
+    /// Rows buffered in memory before they are written as a row group.
+    #[serde(default = "default_row_group_rows")]
+    pub row_group_rows: usize,
+    /// Approximate size of the buffered rows in bytes before they are written as a row group.
+    #[serde(default = "default_row_group_bytes")]
+    pub row_group_bytes: usize,
+    /// Seconds the oldest buffered row may wait before the rows are written as a row group.
+    #[serde(default = "default_row_group_interval")]
+    pub row_group_interval: u64,
+}
+
+fn default_row_group_rows() -> usize {
+    100_000
+}
+
+fn default_row_group_bytes() -> usize {
+    16 * 1024 * 1024
+}
+
+fn default_row_group_interval() -> u64 {
+    60
+}
+
+/// Whether a Parquet row holds one metric or a whole collection cycle.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ParquetSchema {
+    #[default]
+    Long,
+    Wide,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// JSON template for the request body, see the example configuration for the placeholders.
+    pub template: Option<String>,
+    /// JSON template for each element of `{{metrics}}`.
+    pub metric_template: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Sent as a bearer token. Takes precedence over basic auth.
+    pub token: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Request timeout in seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ElasticsearchConfig {
+    /// Base URL of the cluster, e.g. `http://localhost:9200`.
+    pub url: String,
+    /// Index name, with `strftime` patterns expanded from the UTC timestamp of each document.
+    #[serde(default = "default_elasticsearch_index")]
+    pub index: String,
+    #[serde(default)]
+    pub document: ElasticsearchDocument,
+    /// Encoded API key, sent as `Authorization: ApiKey <key>`. Takes precedence over basic auth.
+    pub api_key: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Request timeout in seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Also applies to items rejected with `429`.
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+/// Whether a document holds one metric or all fields of a measurement with the same tags.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ElasticsearchDocument {
+    #[default]
+    Metric,
+    Measurement,
+}
+
+fn default_elasticsearch_index() -> String {
+    "rsysmetrics-%Y.%m.%d".to_string()
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CollectdConfig {
+    #[serde(default = "default_collectd_address")]
+    pub address: String,
+    /// Interval in seconds sent with every value. When unset, the server assumes its own interval.
+    pub interval: Option<u64>,
+    /// Maximum size of one UDP packet in bytes, including the signature or encryption overhead.
+    #[serde(default = "default_collectd_mtu")]
+    pub mtu: usize,
+    #[serde(default)]
+    pub security_level: CollectdSecurityLevel,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CollectdSecurityLevel {
+    #[default]
+    None,
+    Sign,
+    Encrypt,
+}
+
+fn default_collectd_address() -> String {
+    "localhost:25826".to_string()
+}
+
+fn default_collectd_mtu() -> usize {
+    1452
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ZabbixConfig {
+    /// Address of the Zabbix server or proxy trapper.
+    #[serde(default = "default_zabbix_address")]
+    pub address: String,
+    /// Host name as configured in Zabbix. Defaults to the system hostname.
+    pub host: Option<String>,
+    /// Timeout of one request in seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+fn default_zabbix_address() -> String {
+    "localhost:10051".to_string()
 }
 
 fn default_collect_interval() -> u64 {
     15
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_example_config() {
+        let config: Config = toml::from_str(include_str!("../rsysmetrics.toml")).unwrap();
+        assert_eq!(config.exporters().count(), 1);
+    }
+
+    #[test]
+    fn test_exporters_list() {
+        let config: Config = toml::from_str(
+            r#"
+            [exporter.prometheus]
+
+            [[exporters]]
+            [exporters.influxdb]
+            url = "http://a:8428/write"
+
+            [[exporters]]
+            [exporters.influxdb]
+            url = "http://b:8428/write"
+            "#,
+        )
+        .unwrap();
+
+        let exporters: Vec<&Exporter> = config.exporters().collect();
+        assert_eq!(exporters.len(), 3);
+        assert!(matches!(exporters[0], Exporter::Prometheus(_)));
+        assert!(matches!(exporters[1], Exporter::InfluxDB(c) if c.url == "http://a:8428/write"));
+        assert!(matches!(exporters[2], Exporter::InfluxDB(c) if c.url == "http://b:8428/write"));
+    }
+}
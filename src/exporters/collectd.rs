@@ -0,0 +1,385 @@
+use super::statsd::connect_udp;
+use super::{ExportError, Exporter};
+use crate::collectors::{Metric, MetricKind, Value};
+use crate::config::{CollectdConfig, CollectdSecurityLevel};
+use async_trait::async_trait;
+use aes::Aes256;
+use aes::cipher::{BlockEncrypt, KeyInit};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tokio::time::Instant;
+
+const PART_HOST: u16 = 0x0000;
+const PART_PLUGIN: u16 = 0x0002;
+const PART_PLUGIN_INSTANCE: u16 = 0x0003;
+const PART_TYPE: u16 = 0x0004;
+const PART_TYPE_INSTANCE: u16 = 0x0005;
+const PART_VALUES: u16 = 0x0006;
+const PART_TIME_HR: u16 = 0x0008;
+const PART_INTERVAL_HR: u16 = 0x0009;
+const PART_SIGNATURE: u16 = 0x0200;
+const PART_ENCRYPTION: u16 = 0x0210;
+
+const DS_TYPE_COUNTER: u8 = 0;
+const DS_TYPE_GAUGE: u8 = 1;
+const DS_TYPE_DERIVE: u8 = 2;
+
+// collectd identifiers are limited to 63 bytes plus the terminating NUL.
+const MAX_NAME_LEN: usize = 63;
+
+/// Sends metrics to a collectd server in the collectd binary network protocol over UDP.
+///
+/// Each metric becomes a value list with the measurement as the plugin, the tag values as the plugin
+/// instance and the field as the type instance. Gauges use the `gauge` type and counters the `derive`
+/// type, both of which are in collectd's default `types.db`. String values are skipped.
+pub struct CollectdExporter {
+    config: CollectdConfig,
+    hostname: String,
+    socket: UdpSocket,
+}
+
+impl CollectdExporter {
+    pub async fn connect(config: &CollectdConfig, hostname: &str) -> io::Result<Self> {
+        if config.security_level != CollectdSecurityLevel::None && (config.username.is_none() || config.password.is_none()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "collectd signing and encryption require a username and a password",
+            ));
+        }
+        Ok(CollectdExporter {
+            config: config.clone(),
+            hostname: hostname.to_string(),
+            socket: connect_udp(&config.address).await?,
+        })
+    }
+}
+
+#[async_trait]
+impl Exporter for CollectdExporter {
+    fn name(&self) -> &str {
+        "collectd"
+    }
+
+    async fn export(&mut self, metrics: &[Metric], _deadline: Instant) -> Result<(), ExportError> {
+        let username = self.config.username.as_deref().unwrap_or_default();
+        let password = self.config.password.as_deref().unwrap_or_default();
+        let overhead = match self.config.security_level {
+            CollectdSecurityLevel::None => 0,
+            CollectdSecurityLevel::Sign => 4 + 32 + username.len(),
+            CollectdSecurityLevel::Encrypt => 6 + username.len() + 16 + 20,
+        };
+        let interval = self.config.interval.map(Duration::from_secs);
+
+        let packets = pack(metrics, &self.hostname, interval, self.config.mtu.saturating_sub(overhead));
+        for packet in packets {
+            let packet = match self.config.security_level {
+                CollectdSecurityLevel::None => packet,
+                CollectdSecurityLevel::Sign => sign(&packet, username, password),
+                CollectdSecurityLevel::Encrypt => {
+                    // The IV must be unpredictable for OFB, so it comes from the OS and not from fastrand.
+                    let mut iv = [0u8; 16];
+                    getrandom::fill(&mut iv).map_err(io::Error::from)?;
+                    encrypt(&packet, username, password, iv)
+                }
+            };
+            self.socket.send(&packet).await?;
+        }
+        Ok(())
+    }
+}
+
+fn string_part(part_type: u16, value: &str) -> Vec<u8> {
+    let mut part = Vec::with_capacity(5 + value.len());
+    part.extend_from_slice(&part_type.to_be_bytes());
+    part.extend_from_slice(&(5 + value.len() as u16).to_be_bytes());
+    part.extend_from_slice(value.as_bytes());
+    part.push(0);
+    part
+}
+
+fn numeric_part(part_type: u16, value: u64) -> Vec<u8> {
+    let mut part = Vec::with_capacity(12);
+    part.extend_from_slice(&part_type.to_be_bytes());
+    part.extend_from_slice(&12u16.to_be_bytes());
+    part.extend_from_slice(&value.to_be_bytes());
+    part
+}
+
+/// Encodes a single value and returns it with the name of its type. Gauges are little endian
+/// doubles, all other data sources big endian. Unsigned counters keep their full range as `counter`.
+fn values_part(metric: &Metric) -> Option<(&'static str, Vec<u8>)> {
+    let (type_name, ds_type, value) = match (metric.kind, &metric.value) {
+        (_, Value::String(_)) => return None,
+        (MetricKind::Counter, Value::Int(v)) => ("derive", DS_TYPE_DERIVE, v.to_be_bytes()),
+        (MetricKind::Counter, Value::UInt(v)) => ("counter", DS_TYPE_COUNTER, v.to_be_bytes()),
+        (MetricKind::Counter, value) => ("derive", DS_TYPE_DERIVE, (value.as_f64()? as i64).to_be_bytes()),
+        (MetricKind::Gauge, value) => ("gauge", DS_TYPE_GAUGE, value.as_f64()?.to_le_bytes()),
+    };
+    let mut part = Vec::with_capacity(15);
+    part.extend_from_slice(&PART_VALUES.to_be_bytes());
+    part.extend_from_slice(&15u16.to_be_bytes());
+    part.extend_from_slice(&1u16.to_be_bytes());
+    part.push(ds_type);
+    part.extend_from_slice(&value);
+    Some((type_name, part))
+}
+
+/// Converts to collectd's high resolution time, in units of 2^-30 seconds.
+fn hr_time(duration: Duration) -> u64 {
+    ((duration.as_nanos() << 30) / 1_000_000_000) as u64
+}
+
+/// Makes `value` usable as a collectd identifier part. `/` separates the parts of an identifier
+/// and `-` separates the plugin from its instance, so neither may appear in a plugin name.
+fn sanitize(value: &str, extra: &[char]) -> String {
+    let mut name: String = value
+        .chars()
+        .map(|c| if c == '/' || c.is_control() || extra.contains(&c) { '_' } else { c })
+        .collect();
+    if name.len() > MAX_NAME_LEN {
+        let mut end = MAX_NAME_LEN;
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        name.truncate(end);
+    }
+    name
+}
+
+/// Encodes metrics into packets of at most `max_size` bytes.
+///
+/// Like collectd itself, a part is only written when it differs from the previous value list in the
+/// same packet, and every packet starts with the full set of parts.
+fn pack(metrics: &[Metric], hostname: &str, interval: Option<Duration>, max_size: usize) -> Vec<Vec<u8>> {
+    let now = SystemTime::now();
+    let mut packets = Vec::new();
+    let mut packet = Vec::new();
+    let mut previous: Vec<Vec<u8>> = Vec::new();
+
+    for metric in metrics {
+        let Some((type_name, values)) = values_part(metric) else {
+            continue;
+        };
+        // The agent's hostname takes precedence over a `host` tag set by a collector.
+        let instance: Vec<String> = metric
+            .tags
+            .iter()
+            .filter(|(k, _)| k != "host")
+            .map(|(_, v)| sanitize(v, &[]))
+            .collect();
+        let time = metric.timestamp.unwrap_or(now).duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut parts = vec![
+            string_part(PART_HOST, &sanitize(hostname, &[])),
+            numeric_part(PART_TIME_HR, hr_time(time)),
+            string_part(PART_PLUGIN, &sanitize(&metric.measurement, &['-'])),
+            string_part(PART_PLUGIN_INSTANCE, &sanitize(&instance.join("-"), &[])),
+            string_part(PART_TYPE, type_name),
+            string_part(PART_TYPE_INSTANCE, &sanitize(&metric.field, &[])),
+        ];
+        if let Some(interval) = interval {
+            parts.insert(2, numeric_part(PART_INTERVAL_HR, hr_time(interval)));
+        }
+
+        let changed = |previous: &[Vec<u8>]| -> Vec<u8> {
+            let mut encoded: Vec<u8> = parts
+                .iter()
+                .enumerate()
+                .filter(|(i, part)| previous.get(*i) != Some(part))
+                .flat_map(|(_, part)| part.iter().copied())
+                .collect();
+            encoded.extend_from_slice(&values);
+            encoded
+        };
+        let mut encoded = changed(&previous);
+        if !packet.is_empty() && packet.len() + encoded.len() > max_size {
+            packets.push(std::mem::take(&mut packet));
+            encoded = changed(&[]);
+        }
+        packet.extend_from_slice(&encoded);
+        previous = parts;
+    }
+    if !packet.is_empty() {
+        packets.push(packet);
+    }
+    packets
+}
+
+/// Prepends a signature part holding the HMAC-SHA-256 of the username and the packet.
+fn sign(packet: &[u8], username: &str, password: &str) -> Vec<u8> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(password.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(username.as_bytes());
+    mac.update(packet);
+
+    let mut signed = Vec::with_capacity(36 + username.len() + packet.len());
+    signed.extend_from_slice(&PART_SIGNATURE.to_be_bytes());
+    signed.extend_from_slice(&(36 + username.len() as u16).to_be_bytes());
+    signed.extend_from_slice(&mac.finalize().into_bytes());
+    signed.extend_from_slice(username.as_bytes());
+    signed.extend_from_slice(packet);
+    signed
+}
+
+/// Wraps the packet in an encryption part: the SHA-1 of the packet followed by the packet, encrypted
+/// with AES-256 in OFB mode with the SHA-256 of the password as the key.
+fn encrypt(packet: &[u8], username: &str, password: &str, iv: [u8; 16]) -> Vec<u8> {
+    let mut payload = Sha1::digest(packet).to_vec();
+    payload.extend_from_slice(packet);
+    apply_ofb(&mut payload, password, iv);
+
+    let length = 4 + 2 + username.len() + iv.len() + payload.len();
+    let mut encrypted = Vec::with_capacity(length);
+    encrypted.extend_from_slice(&PART_ENCRYPTION.to_be_bytes());
+    encrypted.extend_from_slice(&(length as u16).to_be_bytes());
+    encrypted.extend_from_slice(&(username.len() as u16).to_be_bytes());
+    encrypted.extend_from_slice(username.as_bytes());
+    encrypted.extend_from_slice(&iv);
+    encrypted.extend_from_slice(&payload);
+    encrypted
+}
+
+/// XORs `data` with the AES-256 OFB key stream. Encryption and decryption are the same operation.
+fn apply_ofb(data: &mut [u8], password: &str, iv: [u8; 16]) {
+    let cipher = Aes256::new(&Sha256::digest(password.as_bytes()));
+    let mut block = iv.into();
+    for chunk in data.chunks_mut(16) {
+        cipher.encrypt_block(&mut block);
+        for (byte, key) in chunk.iter_mut().zip(block.iter()) {
+            *byte ^= key;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporters::testutil::deadline;
+
+    fn test_metrics() -> Vec<Metric> {
+        let tags = vec![("core".to_string(), "cpu0".to_string())];
+        let mut metrics = vec![
+            Metric::new("cpu", "usage_user", Value::Float(0.5), tags.clone()),
+            Metric::new("cpu", "usage_system", Value::Float(0.25), tags),
+            Metric::new("disk", "model", Value::String("Disk A".to_string()), vec![]),
+            Metric::new("network", "bytes_recv", Value::UInt(1024), vec![("interface".to_string(), "eth0".to_string())])
+                .with_kind(MetricKind::Counter),
+        ];
+        for metric in &mut metrics {
+            metric.timestamp = Some(UNIX_EPOCH + Duration::from_secs(1678886400));
+        }
+        metrics
+    }
+
+    fn config(address: String, security_level: CollectdSecurityLevel) -> CollectdConfig {
+        CollectdConfig {
+            address,
+            interval: Some(15),
+            mtu: 1452,
+            security_level,
+            username: Some("user".to_string()),
+            password: Some("secret".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_pack() {
+        let packets = pack(&test_metrics()[..1], "test-host", None, 1452);
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b"\x00\x00\x00\x0etest-host\x00");
+        expected.extend_from_slice(b"\x00\x08\x00\x0c");
+        expected.extend_from_slice(&(1678886400u64 << 30).to_be_bytes());
+        expected.extend_from_slice(b"\x00\x02\x00\x08cpu\x00");
+        expected.extend_from_slice(b"\x00\x03\x00\x09cpu0\x00");
+        expected.extend_from_slice(b"\x00\x04\x00\x0agauge\x00");
+        expected.extend_from_slice(b"\x00\x05\x00\x0fusage_user\x00");
+        expected.extend_from_slice(b"\x00\x06\x00\x0f\x00\x01\x01");
+        expected.extend_from_slice(&0.5f64.to_le_bytes());
+        assert_eq!(packets, vec![expected]);
+    }
+
+    #[test]
+    fn test_pack_repeats_only_changed_parts() {
+        let packets = pack(&test_metrics(), "test-host", None, 1452);
+        assert_eq!(packets.len(), 1);
+        // The second metric only changes the type instance; the string metric is skipped.
+        let second = b"\x00\x05\x00\x11usage_system\x00\x00\x06\x00\x0f\x00\x01\x01";
+        let position = packets[0].windows(second.len()).position(|w| w == second);
+        assert!(position.is_some());
+        let counter = b"\x00\x04\x00\x0ccounter\x00\x00\x05\x00\x0fbytes_recv\x00\x00\x06\x00\x0f\x00\x01\x00\x00\x00\x00\x00\x00\x00\x04\x00";
+        assert!(packets[0].ends_with(counter));
+
+        // Every packet is self-contained when the metrics do not fit in one.
+        let packets = pack(&test_metrics(), "test-host", None, 100);
+        assert_eq!(packets.len(), 3);
+        for packet in &packets {
+            assert!(packet.starts_with(b"\x00\x00\x00\x0etest-host\x00"));
+        }
+    }
+
+    #[test]
+    fn test_pack_ignores_host_tag() {
+        let metrics = vec![Metric::new("cpu", "usage", Value::Float(0.5), vec![("host".to_string(), "other".to_string())])];
+        let packets = pack(&metrics, "test-host", None, 1452);
+        assert!(packets[0].starts_with(b"\x00\x00\x00\x0etest-host\x00"));
+        assert!(!packets[0].windows(5).any(|w| w == b"other"));
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("a/b-c", &['-']), "a_b_c");
+        assert_eq!(sanitize(&"é".repeat(40), &[]).len(), 62);
+    }
+
+    #[test]
+    fn test_sign() {
+        let signed = sign(b"payload", "user", "secret");
+        assert_eq!(&signed[..4], b"\x02\x00\x00\x28");
+        assert_eq!(&signed[36..], b"userpayload");
+
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(b"secret").unwrap();
+        mac.update(b"userpayload");
+        mac.verify_slice(&signed[4..36]).unwrap();
+    }
+
+    #[test]
+    fn test_encrypt() {
+        let iv = [7u8; 16];
+        let encrypted = encrypt(b"payload", "user", "secret", iv);
+        assert_eq!(&encrypted[..4], &[0x02, 0x10, 0x00, 4 + 2 + 4 + 16 + 20 + 7]);
+        assert_eq!(&encrypted[4..10], b"\x00\x04user");
+        assert_eq!(&encrypted[10..26], &iv);
+
+        let mut payload = encrypted[26..].to_vec();
+        apply_ofb(&mut payload, "secret", iv);
+        assert_eq!(&payload[..20], Sha1::digest(b"payload").as_slice());
+        assert_eq!(&payload[20..], b"payload");
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let config = config(server.local_addr().unwrap().to_string(), CollectdSecurityLevel::Sign);
+
+        let mut exporter = CollectdExporter::connect(&config, "test-host").await.unwrap();
+        exporter.export(&test_metrics(), deadline()).await.unwrap();
+
+        let mut buf = [0u8; 1500];
+        let n = server.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..2], b"\x02\x00");
+        let packet = &buf[36 + 4..n];
+        assert!(packet.starts_with(b"\x00\x00\x00\x0etest-host\x00\x00\x08\x00\x0c"));
+        // The interval follows the time.
+        assert_eq!(&packet[26..30], b"\x00\x09\x00\x0c");
+        assert_eq!(&packet[30..38], &(15u64 << 30).to_be_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_connect_requires_credentials() {
+        let mut config = config("127.0.0.1:25826".to_string(), CollectdSecurityLevel::Encrypt);
+        config.password = None;
+        assert!(CollectdExporter::connect(&config, "test-host").await.is_err());
+    }
+}
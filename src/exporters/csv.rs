@@ -0,0 +1,237 @@
+use super::rotate::RotatingFile;
+use super::{ExportError, Exporter};
+use crate::collectors::{Metric, Value};
+use crate::config::CsvConfig;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+
+/// Writes one CSV row per collection cycle with a column for every series seen so far.
+///
+/// A CSV header cannot grow once rows follow it. With rotation configured, a series that shows up
+/// mid-run is added to the header at the next rotation by size or age, and left out of the rows
+/// until then, so that churning series do not rotate older files out of `max_files`. Without
+/// rotation, where no file is ever deleted, a new series starts a new file right away. Columns keep
+/// their order across files.
+pub struct CsvExporter {
+    file: RotatingFile,
+    rotates: bool,
+    columns: Vec<String>,
+    indices: HashMap<String, usize>,
+    // Number of columns in the header of the current file.
+    file_columns: usize,
+}
+
+impl CsvExporter {
+    /// Opens the output file. A file left over from an earlier run is rotated away, since its
+    /// columns are not known.
+    pub fn new(config: &CsvConfig) -> io::Result<Self> {
+        let mut file = RotatingFile::open(&config.path, config.rotate.as_ref())?;
+        if !file.is_empty() {
+            file.rotate()?;
+        }
+        Ok(CsvExporter {
+            file,
+            rotates: config.rotate.is_some(),
+            columns: Vec::new(),
+            indices: HashMap::new(),
+            file_columns: 0,
+        })
+    }
+}
+
+#[async_trait]
+impl Exporter for CsvExporter {
+    fn name(&self) -> &str {
+        "csv"
+    }
+
+    async fn export(&mut self, metrics: &[Metric], _deadline: Instant) -> Result<(), ExportError> {
+        if metrics.is_empty() {
+            return Ok(());
+        }
+
+        let mut values = Vec::new();
+        let mut new_series = false;
+        for metric in metrics {
+            let column = column_name(metric);
+            let index = match self.indices.get(&column) {
+                Some(index) => *index,
+                None => {
+                    new_series = true;
+                    self.indices.insert(column.clone(), self.columns.len());
+                    self.columns.push(column);
+                    self.columns.len() - 1
+                }
+            };
+            values.push((index, format_value(&metric.value)));
+        }
+
+        // Every metric of a cycle shares the row, which is stamped with the earliest sample time.
+        let timestamp = metrics
+            .iter()
+            .filter_map(|m| m.timestamp)
+            .min()
+            .unwrap_or_else(SystemTime::now);
+        let mut cells = vec![String::new(); self.columns.len()];
+        for (index, value) in values {
+            cells[index] = value;
+        }
+        let timestamp = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let row = |columns: usize| format!("{},{}\n", timestamp, cells[..columns].join(","));
+
+        if new_series && !self.rotates && !self.file.is_empty() {
+            self.file.rotate()?;
+        }
+        self.file.rotate_if_needed(row(self.columns.len()).len())?;
+        if self.file.is_empty() {
+            self.file_columns = self.columns.len();
+            let header = std::iter::once("timestamp")
+                .chain(self.columns.iter().map(String::as_str))
+                .map(escape)
+                .collect::<Vec<_>>()
+                .join(",");
+            self.file.write(format!("{}\n{}", header, row(self.file_columns)).as_bytes())?;
+        } else {
+            self.file.write(row(self.file_columns).as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// Names the column of a series, e.g. `cpu_usage_user{core=cpu3}`. Tags are sorted by key.
+pub(crate) fn column_name(metric: &Metric) -> String {
+    if metric.tags.is_empty() {
+        return metric.name();
+    }
+    let mut tags = metric.tags.clone();
+    tags.sort();
+    let tags = tags
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!("{}{{{}}}", metric.name(), tags)
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::Int(v) => v.to_string(),
+        Value::UInt(v) => v.to_string(),
+        Value::Float(v) => v.to_string(),
+        Value::Bool(v) => (*v as u8).to_string(),
+        Value::String(v) => escape(v),
+    }
+}
+
+/// Quotes a cell if it contains a separator, quote or line break.
+fn escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RotateConfig;
+    use crate::exporters::testutil::deadline;
+    use std::fs;
+    use std::time::Duration;
+
+    fn stamped(mut metrics: Vec<Metric>, secs: u64) -> Vec<Metric> {
+        for metric in &mut metrics {
+            metric.timestamp = Some(UNIX_EPOCH + Duration::from_secs(secs));
+        }
+        metrics
+    }
+
+    fn cpu(core: &str, value: f64) -> Metric {
+        Metric::new("cpu", "usage_user", Value::Float(value), vec![("core".to_string(), core.to_string())])
+    }
+
+    #[test]
+    fn test_column_name() {
+        let metric = Metric::new(
+            "disk",
+            "read_bytes",
+            Value::UInt(1),
+            vec![("mount".to_string(), "/".to_string()), ("device".to_string(), "sda".to_string())],
+        );
+        assert_eq!(column_name(&metric), "disk_read_bytes{device=sda,mount=/}");
+        assert_eq!(column_name(&Metric::new("memory", "total", Value::UInt(1), vec![])), "memory_total");
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.csv");
+        let config = CsvConfig {
+            path: path.to_string_lossy().into_owned(),
+            rotate: None,
+        };
+        fs::write(&path, "old run\n").unwrap();
+
+        let mut exporter = CsvExporter::new(&config).unwrap();
+        let metrics = vec![cpu("cpu0", 0.5), Metric::new("disk", "model", Value::String("A, \"B\"".to_string()), vec![])];
+        exporter.export(&stamped(metrics, 100), deadline()).await.unwrap();
+        exporter.export(&stamped(vec![cpu("cpu0", 1.5)], 115), deadline()).await.unwrap();
+
+        assert_eq!(fs::read_to_string(dir.path().join("metrics.csv.1")).unwrap(), "old run\n");
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "timestamp,cpu_usage_user{core=cpu0},disk_model\n\
+             100000,0.5,\"A, \"\"B\"\"\"\n\
+             115000,1.5,\n"
+        );
+
+        // A new series starts a new file with the extended header.
+        exporter.export(&stamped(vec![cpu("cpu1", 2.5), cpu("cpu0", 3.5)], 130), deadline()).await.unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("metrics.csv.1")).unwrap().lines().count(), 3);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "timestamp,cpu_usage_user{core=cpu0},disk_model,cpu_usage_user{core=cpu1}\n\
+             130000,3.5,,2.5\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_new_series_waits_for_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.csv");
+        let config = CsvConfig {
+            path: path.to_string_lossy().into_owned(),
+            rotate: Some(RotateConfig {
+                max_size: Some(90),
+                max_age: None,
+                max_files: 1,
+            }),
+        };
+
+        let mut exporter = CsvExporter::new(&config).unwrap();
+        exporter.export(&stamped(vec![cpu("cpu0", 0.5)], 100), deadline()).await.unwrap();
+        // The new series does not rotate the file, its values are left out until the next rotation.
+        exporter.export(&stamped(vec![cpu("cpu1", 1.5), cpu("cpu0", 2.5)], 115), deadline()).await.unwrap();
+        assert!(!dir.path().join("metrics.csv.1").exists());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "timestamp,cpu_usage_user{core=cpu0}\n\
+             100000,0.5\n\
+             115000,2.5\n"
+        );
+
+        for secs in [130, 145, 160] {
+            exporter.export(&stamped(vec![cpu("cpu1", 1.5), cpu("cpu0", 2.5)], secs), deadline()).await.unwrap();
+        }
+        assert_eq!(fs::read_to_string(dir.path().join("metrics.csv.1")).unwrap().lines().count(), 5);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "timestamp,cpu_usage_user{core=cpu0},cpu_usage_user{core=cpu1}\n\
+             160000,2.5,1.5\n"
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use super::retry::{backoff, with_retry};
+use super::sqlite::format_tags;
+use super::{ExportError, Exporter};
+use crate::collectors::{Metric, Value};
+use crate::config::{ElasticsearchConfig, ElasticsearchDocument};
+use async_trait::async_trait;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::Client;
+use serde_json::{Map, json};
+use std::io;
+use std::time::{Duration, SystemTime};
+use tokio::time::{self, Instant};
+
+/// Indexes metrics in Elasticsearch or OpenSearch through the `_bulk` API.
+pub struct ElasticsearchExporter {
+    client: Client,
+    config: ElasticsearchConfig,
+    hostname: String,
+}
+
+/// A document together with the index it is written to.
+struct Document {
+    index: String,
+    source: serde_json::Value,
+}
+
+impl ElasticsearchExporter {
+    /// Fails if the index pattern is not a valid `strftime` format, which chrono would only report
+    /// by panicking when formatting.
+    pub fn new(client: &Client, config: &ElasticsearchConfig, hostname: &str) -> io::Result<Self> {
+        if StrftimeItems::new(&config.index).any(|item| item == Item::Error) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid Elasticsearch index pattern '{}'", config.index),
+            ));
+        }
+        Ok(ElasticsearchExporter {
+            client: client.clone(),
+            config: config.clone(),
+            hostname: hostname.to_string(),
+        })
+    }
+
+    /// Sends one bulk request and returns the outcome of every item, in order.
+    async fn send(&self, documents: &[Document], deadline: Instant) -> Result<Vec<Result<(), (u16, String)>>, ExportError> {
+        let mut body = String::new();
+        for document in documents {
+            body.push_str(&json!({"create": {"_index": document.index}}).to_string());
+            body.push('\n');
+            body.push_str(&document.source.to_string());
+            body.push('\n');
+        }
+
+        let url = format!("{}/_bulk", self.config.url.trim_end_matches('/'));
+        let response = with_retry(&self.config.retry, deadline, || async {
+            let mut request_builder = self
+                .client
+                .post(&url)
+                .timeout(Duration::from_secs(self.config.timeout))
+                .header("Content-Type", "application/x-ndjson");
+            if let Some(api_key) = &self.config.api_key {
+                request_builder = request_builder.header("Authorization", format!("ApiKey {}", api_key));
+            } else if let Some(username) = &self.config.username {
+                request_builder = request_builder.basic_auth(username, self.config.password.as_ref());
+            }
+            request_builder.body(body.clone()).send().await?.error_for_status()?.bytes().await
+        })
+        .await?;
+
+        let response: serde_json::Value = serde_json::from_slice(&response).map_err(io::Error::other)?;
+        Ok(parse_items(&response, documents.len()))
+    }
+}
+
+#[async_trait]
+impl Exporter for ElasticsearchExporter {
+    fn name(&self) -> &str {
+        "elasticsearch"
+    }
+
+    /// Sends the documents and resends the items rejected with `429` until the retries are exhausted
+    /// or the deadline leaves no time for another attempt. Items rejected for any other reason are
+    /// dropped and reported.
+    async fn export(&mut self, metrics: &[Metric], deadline: Instant) -> Result<(), ExportError> {
+        let mut documents = build_documents(metrics, &self.hostname, &self.config);
+        let total = documents.len();
+        let mut rejected = Vec::new();
+        let mut attempt = 0;
+
+        while !documents.is_empty() {
+            let started = Instant::now();
+            let results = self.send(&documents, deadline).await?;
+            let mut retry = Vec::new();
+            for (document, result) in documents.into_iter().zip(results) {
+                match result {
+                    Ok(()) => {}
+                    Err((429, _)) if attempt < self.config.retry.max_retries => retry.push(document),
+                    Err((_, reason)) => rejected.push(reason),
+                }
+            }
+            documents = retry;
+            if !documents.is_empty() {
+                let delay = backoff(&self.config.retry, attempt);
+                if Instant::now() + delay + started.elapsed() > deadline {
+                    let reason = "throttled, no time left to retry before the export deadline";
+                    rejected.extend(documents.drain(..).map(|_| reason.to_string()));
+                    break;
+                }
+                eprintln!("[Warning] {} documents were throttled, retrying in {:?}", documents.len(), delay);
+                time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+
+        if rejected.is_empty() {
+            return Ok(());
+        }
+        Err(ExportError::Rejected {
+            failed: rejected.len(),
+            total,
+            reason: rejected.swap_remove(0),
+        })
+    }
+}
+
+/// Extracts the outcome of every item from a bulk response.
+///
+/// Items missing from the response are treated as rejected.
+fn parse_items(response: &serde_json::Value, count: usize) -> Vec<Result<(), (u16, String)>> {
+    let items = response["items"].as_array().map(Vec::as_slice).unwrap_or_default();
+    (0..count)
+        .map(|i| {
+            let Some(item) = items.get(i).and_then(|item| item.as_object()).and_then(|item| item.values().next()) else {
+                return Err((0, "missing from bulk response".to_string()));
+            };
+            let status = item["status"].as_u64().unwrap_or(0) as u16;
+            if (200..300).contains(&status) {
+                Ok(())
+            } else {
+                let reason = format!(
+                    "{} {}: {}",
+                    status,
+                    item["error"]["type"].as_str().unwrap_or("error"),
+                    item["error"]["reason"].as_str().unwrap_or_default()
+                );
+                Err((status, reason))
+            }
+        })
+        .collect()
+}
+
+/// Converts a value to JSON. Integers are written as they are, so they stay exact beyond 2^53.
+fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Int(v) => json!(v),
+        Value::UInt(v) => json!(v),
+        Value::String(v) => json!(v),
+        value => json!(value.as_f64()),
+    }
+}
+
+/// Builds the documents for a cycle, one per metric or one per measurement, tag set and timestamp.
+///
+/// Numeric values are stored in `value` and strings in `value_text`, so the two never conflict in the
+/// index mapping. Grouped documents hold the fields under the measurement name, e.g. `cpu.usage`.
+fn build_documents(metrics: &[Metric], hostname: &str, config: &ElasticsearchConfig) -> Vec<Document> {
+    let now = SystemTime::now();
+    let mut documents: Vec<(String, Document)> = Vec::new();
+
+    for metric in metrics {
+        let time = DateTime::<Utc>::from(metric.timestamp.unwrap_or(now));
+        let index = time.format(&config.index).to_string();
+        let tags: Map<String, serde_json::Value> = metric
+            .tags
+            .iter()
+            .filter(|(k, _)| k != "host")
+            .map(|(k, v)| (k.clone(), json!(v)))
+            .collect();
+        let mut source = json!({
+            "@timestamp": time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            "host": {"name": hostname},
+            "measurement": metric.measurement,
+            "tags": tags,
+        });
+
+        match config.document {
+            ElasticsearchDocument::Metric => {
+                source["name"] = json!(metric.name());
+                source["field"] = json!(metric.field);
+                match &metric.value {
+                    Value::String(v) => source["value_text"] = json!(v),
+                    value => source["value"] = json_value(value),
+                }
+                documents.push((String::new(), Document { index, source }));
+            }
+            ElasticsearchDocument::Measurement => {
+                let key = format!("{}\0{}\0{}", metric.measurement, format_tags(&metric.tags), source["@timestamp"]);
+                let position = match documents.iter().position(|(k, _)| *k == key) {
+                    Some(position) => position,
+                    None => {
+                        source[&metric.measurement] = json!({});
+                        documents.push((key, Document { index, source }));
+                        documents.len() - 1
+                    }
+                };
+                documents[position].1.source[&metric.measurement][&metric.field] = json_value(&metric.value);
+            }
+        }
+    }
+
+    documents.into_iter().map(|(_, document)| document).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RetryConfig;
+    use crate::exporters::testutil::{deadline, http_server};
+    use std::time::UNIX_EPOCH;
+
+    fn es_config(url: String, document: ElasticsearchDocument) -> ElasticsearchConfig {
+        ElasticsearchConfig {
+            url,
+            index: "rsysmetrics-%Y.%m.%d".to_string(),
+            document,
+            api_key: None,
+            username: None,
+            password: None,
+            timeout: 10,
+            retry: RetryConfig {
+                max_retries: 3,
+                initial_backoff: 1,
+                max_backoff: 10,
+            },
+        }
+    }
+
+    fn test_metrics() -> Vec<Metric> {
+        let tags = vec![("core".to_string(), "cpu0".to_string())];
+        let mut metrics = vec![
+            Metric::new("cpu", "usage_user", Value::Float(0.5), tags.clone()),
+            Metric::new("cpu", "usage_system", Value::Float(0.25), tags),
+            Metric::new("disk", "model", Value::String("Disk A".to_string()), vec![]),
+        ];
+        for metric in &mut metrics {
+            metric.timestamp = Some(UNIX_EPOCH + Duration::from_millis(1678886400123));
+        }
+        metrics
+    }
+
+    #[test]
+    fn test_build_documents_per_metric() {
+        let config = es_config(String::new(), ElasticsearchDocument::Metric);
+        let documents = build_documents(&test_metrics(), "test-host", &config);
+
+        assert_eq!(documents.len(), 3);
+        assert_eq!(documents[0].index, "rsysmetrics-2023.03.15");
+        assert_eq!(
+            documents[0].source,
+            json!({
+                "@timestamp": "2023-03-15T13:20:00.123Z",
+                "host": {"name": "test-host"},
+                "measurement": "cpu",
+                "name": "cpu_usage_user",
+                "field": "usage_user",
+                "value": 0.5,
+                "tags": {"core": "cpu0"},
+            })
+        );
+        assert_eq!(documents[2].source["value_text"], json!("Disk A"));
+    }
+
+    #[test]
+    fn test_build_documents_per_measurement() {
+        let config = es_config(String::new(), ElasticsearchDocument::Measurement);
+        let documents = build_documents(&test_metrics(), "test-host", &config);
+
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].source["cpu"], json!({"usage_user": 0.5, "usage_system": 0.25}));
+        assert_eq!(documents[0].source["tags"], json!({"core": "cpu0"}));
+        assert_eq!(documents[1].source["disk"], json!({"model": "Disk A"}));
+    }
+
+    #[test]
+    fn test_build_documents_exact_integers() {
+        let config = es_config(String::new(), ElasticsearchDocument::Metric);
+        let metrics = vec![
+            Metric::new("disk", "read_bytes", Value::UInt(u64::MAX), vec![]),
+            Metric::new("disk", "offset", Value::Int(i64::MIN), vec![]),
+        ];
+        let documents = build_documents(&metrics, "test-host", &config);
+        assert_eq!(documents[0].source["value"], json!(u64::MAX));
+        assert_eq!(documents[1].source["value"], json!(i64::MIN));
+    }
+
+    #[test]
+    fn test_invalid_index_pattern() {
+        let mut config = es_config(String::new(), ElasticsearchDocument::Metric);
+        config.index = "metrics-%Q".to_string();
+        let error = ElasticsearchExporter::new(&Client::new(), &config, "test-host").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn test_export_item_errors() {
+        let (url, requests) = http_server(vec![
+            (
+                200,
+                r#"{"errors": true, "items": [
+                    {"create": {"status": 201}},
+                    {"create": {"status": 429, "error": {"type": "es_rejected_execution_exception", "reason": "queue full"}}},
+                    {"create": {"status": 400, "error": {"type": "mapper_parsing_exception", "reason": "failed to parse"}}}
+                ]}"#,
+            ),
+            (200, r#"{"errors": false, "items": [{"create": {"status": 201}}]}"#),
+        ])
+        .await;
+        let mut config = es_config(url, ElasticsearchDocument::Metric);
+        config.api_key = Some("a2V5".to_string());
+
+        let mut exporter = ElasticsearchExporter::new(&Client::new(), &config, "test-host").unwrap();
+        let error = exporter.export(&test_metrics(), deadline()).await.unwrap_err();
+        assert!(matches!(&error, ExportError::Rejected { failed: 1, total: 3, reason } if reason.contains("mapper_parsing_exception")));
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].target, "/_bulk");
+        assert_eq!(requests[0].header("authorization"), Some("ApiKey a2V5"));
+        assert_eq!(requests[0].header("content-type"), Some("application/x-ndjson"));
+        let body = String::from_utf8(requests[0].body.clone()).unwrap();
+        assert_eq!(body.lines().count(), 6);
+        assert_eq!(body.lines().next(), Some(r#"{"create":{"_index":"rsysmetrics-2023.03.15"}}"#));
+        // Only the throttled item is sent again.
+        let body = String::from_utf8(requests[1].body.clone()).unwrap();
+        assert_eq!(body.lines().count(), 2);
+        assert!(body.contains("usage_system"));
+    }
+}
@@ -0,0 +1,259 @@
+use super::{ExportError, Exporter};
+use crate::collectors::{Metric, Value};
+use crate::config::GraphiteConfig;
+use async_trait::async_trait;
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::time::{self, Instant};
+
+/// Writes metrics to Carbon in the Graphite plaintext protocol over a persistent TCP connection.
+pub struct GraphiteExporter {
+    config: GraphiteConfig,
+    hostname: String,
+    stream: Option<TcpStream>,
+}
+
+impl GraphiteExporter {
+    pub fn new(config: &GraphiteConfig, hostname: &str) -> Self {
+        GraphiteExporter {
+            config: config.clone(),
+            hostname: hostname.to_string(),
+            stream: None,
+        }
+    }
+
+    async fn connect(&self) -> io::Result<TcpStream> {
+        let timeout = Duration::from_secs(self.config.timeout);
+        match time::timeout(timeout, TcpStream::connect(&self.config.address)).await {
+            Ok(stream) => stream,
+            Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "connection to Carbon timed out")),
+        }
+    }
+}
+
+#[async_trait]
+impl Exporter for GraphiteExporter {
+    fn name(&self) -> &str {
+        "graphite"
+    }
+
+    async fn export(&mut self, metrics: &[Metric], deadline: Instant) -> Result<(), ExportError> {
+        let lines = format_metrics(metrics, &self.hostname, &self.config);
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        // The stream is taken for the write and only put back once the write completed. A write
+        // that failed or was cancelled may have left part of a line on the wire, and resending on
+        // the same or a new connection could garble or duplicate lines, so the connection is dropped.
+        let mut stream = match self.stream.take() {
+            Some(stream) if is_open(&stream) => stream,
+            // Carbon closed the idle connection, nothing of this cycle was sent yet.
+            _ => self.connect().await?,
+        };
+        match time::timeout_at(deadline, stream.write_all(lines.as_bytes())).await {
+            Ok(Ok(())) => {
+                self.stream = Some(stream);
+                Ok(())
+            }
+            Ok(Err(e)) => Err(e.into()),
+            Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "write to Carbon timed out").into()),
+        }
+    }
+}
+
+/// Returns false if Carbon closed the connection. Carbon never sends anything, so readable means closed.
+fn is_open(stream: &TcpStream) -> bool {
+    let mut buf = [0u8; 1];
+    matches!(stream.try_read(&mut buf), Err(e) if e.kind() == io::ErrorKind::WouldBlock)
+}
+
+/// Replaces characters that would split or break a Graphite path segment.
+fn sanitize_segment(segment: &str) -> String {
+    segment
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// Splits a path template at the dots that are not inside a `{...}` placeholder.
+fn split_template(template: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in template.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            '.' if depth == 0 => {
+                segments.push(&template[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    segments.push(&template[start..]);
+    segments
+}
+
+/// Expands the path template for one metric.
+///
+/// `{host}`, `{name}`, `{measurement}` and `{field}` are replaced by the respective segment,
+/// `{tag.KEY}` by the value of tag `KEY`, and `{tags}` by the values of the configured `tags`
+/// (all tags if unset). Placeholders without a value are left out of the path.
+fn format_path(metric: &Metric, hostname: &str, config: &GraphiteConfig) -> String {
+    let tags: Vec<String> = match &config.tags {
+        Some(keys) => keys
+            .iter()
+            .filter_map(|key| metric.tags.iter().find(|(k, _)| k == key))
+            .map(|(_, v)| sanitize_segment(v))
+            .collect(),
+        None => metric.tags.iter().map(|(_, v)| sanitize_segment(v)).collect(),
+    };
+
+    let mut path = Vec::new();
+    for segment in split_template(&config.template) {
+        let expanded = match segment {
+            "{host}" => sanitize_segment(hostname),
+            "{name}" => sanitize_segment(&metric.name()),
+            "{measurement}" => sanitize_segment(&metric.measurement),
+            "{field}" => sanitize_segment(&metric.field),
+            "{tags}" => tags.join("."),
+            _ => match segment.strip_prefix("{tag.").and_then(|s| s.strip_suffix('}')) {
+                Some(key) => metric
+                    .tags
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| sanitize_segment(v))
+                    .unwrap_or_default(),
+                None => segment.to_string(),
+            },
+        };
+        if !expanded.is_empty() {
+            path.push(expanded);
+        }
+    }
+    path.join(".")
+}
+
+fn format_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Int(v) => Some(v.to_string()),
+        Value::UInt(v) => Some(v.to_string()),
+        Value::Float(v) if v.is_finite() => Some(v.to_string()),
+        Value::Bool(v) => Some((*v as u8).to_string()),
+        Value::Float(_) | Value::String(_) => None,
+    }
+}
+
+/// Formats metrics as `path value timestamp` lines. String and non-finite values are skipped.
+pub fn format_metrics(metrics: &[Metric], hostname: &str, config: &GraphiteConfig) -> String {
+    let now = SystemTime::now();
+    let mut output = String::new();
+    for metric in metrics {
+        let Some(value) = format_value(&metric.value) else {
+            continue;
+        };
+        let timestamp = metric.timestamp.unwrap_or(now).duration_since(UNIX_EPOCH).unwrap_or_default();
+        output.push_str(&format!(
+            "{} {} {}\n",
+            format_path(metric, hostname, config),
+            value,
+            timestamp.as_secs()
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporters::testutil::deadline;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    fn graphite_config(address: String, template: &str, tags: Option<Vec<&str>>) -> GraphiteConfig {
+        GraphiteConfig {
+            address,
+            template: template.to_string(),
+            tags: tags.map(|tags| tags.iter().map(|t| t.to_string()).collect()),
+            timeout: 10,
+        }
+    }
+
+    fn test_metrics() -> Vec<Metric> {
+        let mut metrics = vec![
+            Metric::new(
+                "cpu",
+                "usage",
+                Value::Float(0.5),
+                vec![("core".to_string(), "cpu0".to_string()), ("mode".to_string(), "user".to_string())],
+            ),
+            Metric::new("disk", "model", Value::String("Disk A".to_string()), vec![]),
+            Metric::new("memory", "total", Value::UInt(1024), vec![]),
+        ];
+        for metric in &mut metrics {
+            metric.timestamp = Some(UNIX_EPOCH + Duration::from_secs(1678886400));
+        }
+        metrics
+    }
+
+    #[test]
+    fn test_format_metrics() {
+        let config = graphite_config(String::new(), "servers.{host}.{measurement}.{tags}.{field}", None);
+        let formatted = format_metrics(&test_metrics(), "web.example.com", &config);
+        let expected = "servers.web_example_com.cpu.cpu0.user.usage 0.5 1678886400\n\
+            servers.web_example_com.memory.total 1024 1678886400\n";
+        assert_eq!(formatted, expected);
+    }
+
+    #[test]
+    fn test_format_path_chosen_tags() {
+        let metric = &test_metrics()[0];
+
+        let config = graphite_config(String::new(), "{host}.{name}.{tags}", Some(vec!["mode", "device"]));
+        assert_eq!(format_path(metric, "host", &config), "host.cpu_usage.user");
+
+        let config = graphite_config(String::new(), "{host}.{tag.core}.{tag.device}.{field}", None);
+        assert_eq!(format_path(metric, "host", &config), "host.cpu0.usage");
+    }
+
+    #[tokio::test]
+    async fn test_export_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = graphite_config(listener.local_addr().unwrap().to_string(), "{host}.{name}", None);
+        let mut exporter = GraphiteExporter::new(&config, "host");
+        let metrics = &test_metrics()[2..];
+
+        exporter.export(metrics, deadline()).await.unwrap();
+        let (mut connection, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 1024];
+        let n = connection.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"host.memory_total 1024 1678886400\n");
+
+        // Carbon restarts and the agent has to notice on the next cycle.
+        drop(connection);
+        time::sleep(Duration::from_millis(50)).await;
+
+        exporter.export(metrics, deadline()).await.unwrap();
+        let (mut connection, _) = listener.accept().await.unwrap();
+        let n = connection.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"host.memory_total 1024 1678886400\n");
+    }
+
+    #[tokio::test]
+    async fn test_export_drops_connection_after_partial_write() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = graphite_config(listener.local_addr().unwrap().to_string(), "{host}.{name}", None);
+        let mut exporter = GraphiteExporter::new(&config, "host");
+
+        // Carbon does not read, so a large cycle cannot be written before the deadline.
+        let metrics: Vec<Metric> = (0..500_000).map(|i| Metric::new("memory", "total", Value::UInt(i), vec![])).collect();
+        let error = exporter.export(&metrics, Instant::now() + Duration::from_millis(200)).await.unwrap_err();
+        assert!(matches!(error, ExportError::Io(e) if e.kind() == io::ErrorKind::TimedOut));
+        assert!(exporter.stream.is_none());
+        let _connection = listener.accept().await.unwrap();
+    }
+}
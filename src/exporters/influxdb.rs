@@ -1,62 +1,272 @@
-use crate::collectors::Metric;
-use crate::config::InfluxDBConfig;
+use super::retry::{is_transient, with_retry};
+use super::spool::Spool;
+use super::{ExportError, Exporter};
+use crate::collectors::{Metric, Value};
+use crate::config::{InfluxDBConfig, Precision};
+use async_trait::async_trait;
+use flate2::Compression;
+use flate2::write::GzEncoder;
 use reqwest::Client;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
 
-/// Escapes special characters in InfluxDB tag values.
-fn escape_tag_value(value: &str) -> String {
-    value
-        .replace('\\', "\\\\")
-        .replace(' ', "\\ ")
-        .replace(',', "\\,")
-        .replace('=', "\\=")
+/// Sends metrics to InfluxDB, optionally spooling batches that could not be delivered.
+pub struct InfluxDBExporter {
+    client: Client,
+    config: InfluxDBConfig,
+    hostname: String,
+    spool: Option<Spool>,
 }
 
-/// Formats a slice of metrics into InfluxDB line protocol format.
-pub fn format_metrics(metrics: &[Metric], hostname: &str, timestamp: u64) -> String {
-    if metrics.is_empty() {
-        return String::new();
+impl InfluxDBExporter {
+    pub fn new(client: &Client, config: &InfluxDBConfig, hostname: &str) -> io::Result<Self> {
+        let spool = config.spool.as_ref().map(Spool::open).transpose()?;
+        Ok(InfluxDBExporter {
+            client: client.clone(),
+            config: config.clone(),
+            hostname: hostname.to_string(),
+            spool,
+        })
+    }
+}
+
+#[async_trait]
+impl Exporter for InfluxDBExporter {
+    fn name(&self) -> &str {
+        "influxdb"
+    }
+
+    async fn export(&mut self, metrics: &[Metric], deadline: Instant) -> Result<(), ExportError> {
+        let lines = format_metrics(metrics, &self.hostname, self.config.precision);
+        let batches = split_batches(&lines, self.config.max_batch_lines, self.config.max_batch_bytes);
+
+        let Some(spool) = &mut self.spool else {
+            // Without a spool there is no order to preserve, so one failed batch does not stop the rest.
+            let mut failed = Vec::new();
+            for (index, batch) in batches.iter().enumerate() {
+                if let Err(e) = send(&self.client, &self.config, batch, deadline).await {
+                    failed.push((index, ExportError::from(e)));
+                }
+            }
+            if failed.is_empty() {
+                return Ok(());
+            }
+            return Err(ExportError::Batches {
+                total: batches.len(),
+                failed,
+            });
+        };
+
+        if spool.entries()?.is_empty() {
+            // Nothing is waiting, so the batches are sent directly. The first batch that cannot be
+            // delivered is spooled together with all batches after it, so they are replayed in order.
+            let mut failed = Vec::new();
+            let mut spooling = false;
+            for (index, batch) in batches.iter().enumerate() {
+                if spooling {
+                    spool.push(batch)?;
+                    continue;
+                }
+                match send(&self.client, &self.config, batch, deadline).await {
+                    Ok(()) => {}
+                    Err(e) if !is_transient(&e) => failed.push((index, ExportError::from(e))),
+                    Err(e) => {
+                        spool.push(batch)?;
+                        spooling = true;
+                        failed.push((index, ExportError::from(e)));
+                    }
+                }
+            }
+            if failed.is_empty() {
+                return Ok(());
+            }
+            return Err(ExportError::Batches {
+                total: batches.len(),
+                failed,
+            });
+        }
+
+        // The batches join the backlog and are replayed from there, so they are never sent before it.
+        for batch in &batches {
+            spool.push(batch)?;
+        }
+        for entry in spool.entries()? {
+            // Entries spooled under a larger batch limit are split again. If a later part fails,
+            // the earlier parts are sent twice, which InfluxDB treats as an overwrite of the same points.
+            let lines = entry.read()?;
+            for batch in split_batches(&lines, self.config.max_batch_lines, self.config.max_batch_bytes) {
+                match send(&self.client, &self.config, &batch, deadline).await {
+                    Ok(()) => {}
+                    Err(e) if !is_transient(&e) => {
+                        eprintln!("[Error] InfluxDB rejected part of spooled batch {}, dropping it: {}", entry.seq, e);
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            spool.remove(&entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits newline separated lines into batches of at most `max_lines` lines and `max_bytes` bytes.
+///
+/// A single line longer than `max_bytes` is sent as a batch of its own.
+pub fn split_batches(lines: &str, max_lines: usize, max_bytes: usize) -> Vec<String> {
+    let mut batches = Vec::new();
+    let mut batch = String::new();
+    let mut batch_lines = 0;
+
+    for line in lines.lines().filter(|l| !l.is_empty()) {
+        let separator = if batch.is_empty() { 0 } else { 1 };
+        if batch_lines > 0 && (batch_lines >= max_lines || batch.len() + separator + line.len() > max_bytes) {
+            batches.push(std::mem::take(&mut batch));
+            batch_lines = 0;
+        }
+        if !batch.is_empty() {
+            batch.push('\n');
+        }
+        batch.push_str(line);
+        batch_lines += 1;
+    }
+
+    if !batch.is_empty() {
+        batches.push(batch);
     }
+    batches
+}
 
-    let mut lines = Vec::new();
-    let mut current_line = String::new();
-    let mut last_measurement = String::new();
-    let mut last_tags = String::new();
+/// Sends a batch, retrying transient failures with backoff.
+async fn send(client: &Client, config: &InfluxDBConfig, lines: &str, deadline: Instant) -> Result<(), reqwest::Error> {
+    with_retry(&config.retry, deadline, || export_metrics(client, config, lines)).await
+}
+
+/// Escapes `chars` with a backslash, as line protocol requires.
+///
+/// `chars` should include the backslash itself, otherwise a value ending in a backslash would escape
+/// the separator that follows it. Line breaks and tabs have no escape that InfluxDB decodes, and
+/// would end the line or separate its parts, so they are replaced with a space.
+fn escape(value: &str, chars: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        let c = if matches!(c, '\n' | '\r' | '\t') { ' ' } else { c };
+        if chars.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escapes special characters in InfluxDB measurement names.
+fn escape_measurement(value: &str) -> String {
+    escape(value, &['\\', ',', ' '])
+}
+
+/// Escapes special characters in InfluxDB tag keys, tag values and field keys.
+fn escape_key(value: &str) -> String {
+    escape(value, &['\\', ',', '=', ' '])
+}
+
+/// Formats a field value with the type suffix or quoting InfluxDB expects.
+///
+/// Returns `None` for NaN and infinite floats, which line protocol cannot represent.
+fn format_field_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Int(v) => Some(format!("{}i", v)),
+        Value::UInt(v) => Some(format!("{}u", v)),
+        Value::Float(v) if v.is_finite() => Some(v.to_string()),
+        Value::Float(_) => None,
+        Value::Bool(v) => Some(v.to_string()),
+        Value::String(v) => Some(format!("\"{}\"", escape(v, &['\\', '"']))),
+    }
+}
+
+/// The escaped measurement and tag set of a line, together with its timestamp.
+type SeriesKey = (String, u128);
+
+/// Converts a timestamp to an integer in the given precision.
+fn format_timestamp(timestamp: SystemTime, precision: Precision) -> u128 {
+    let since_epoch = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+    match precision {
+        Precision::S => since_epoch.as_secs() as u128,
+        Precision::Ms => since_epoch.as_millis(),
+        Precision::Us => since_epoch.as_micros(),
+        Precision::Ns => since_epoch.as_nanos(),
+    }
+}
+
+/// Formats a slice of metrics into InfluxDB line protocol format.
+///
+/// All fields sharing a measurement, tag set and timestamp are merged into one line, regardless
+/// of the order in which collectors emitted them. Lines keep the order of their first metric.
+/// Metrics without a timestamp are stamped with the current time.
+pub fn format_metrics(metrics: &[Metric], hostname: &str, precision: Precision) -> String {
+    let now = SystemTime::now();
+    let mut series: Vec<(SeriesKey, Vec<(String, String)>)> = Vec::new();
+    let mut index: HashMap<SeriesKey, usize> = HashMap::new();
 
     for metric in metrics {
-        let mut parts = metric.name.splitn(2, '_');
-        let measurement = parts.next().unwrap_or(&metric.name).to_string();
-        let field = parts.next().unwrap_or("value").to_string();
+        if metric.measurement.is_empty() || metric.field.is_empty() {
+            continue;
+        }
+        let Some(value) = format_field_value(&metric.value) else {
+            continue;
+        };
 
-        let mut tags = metric.tags.clone();
-        tags.sort_by(|a, b| a.0.cmp(&b.0));
-        let tags_str = tags
+        // Tags are sorted by key, as recommended for write performance. The agent's
+        // hostname takes precedence over a `host` tag set by a collector.
+        let mut tags: Vec<(&str, &str)> = metric
+            .tags
             .iter()
-            .map(|(k, v)| format!( ",{}={}", k, escape_tag_value(v)))
-            .collect::<String>();
-
-        if measurement == last_measurement && tags_str == last_tags {
-            // Same group, append field
-            current_line.push_str(&format!( ",{}={}", field, metric.value));
-        } else {
-            // New group, finalize previous line (if any) and start a new one
-            if !current_line.is_empty() {
-                lines.push(format!("{} {}", current_line, timestamp));
+            .filter(|(k, v)| !k.is_empty() && !v.is_empty() && k != "host")
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        tags.push(("host", hostname));
+        tags.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut key = escape_measurement(&metric.measurement);
+        for (k, v) in tags {
+            key.push_str(&format!(",{}={}", escape_key(k), escape_key(v)));
+        }
+
+        let key = (key, format_timestamp(metric.timestamp.unwrap_or(now), precision));
+        let field = escape_key(&metric.field);
+        let fields = match index.get(&key) {
+            Some(&i) => &mut series[i].1,
+            None => {
+                index.insert(key.clone(), series.len());
+                series.push((key, Vec::new()));
+                &mut series.last_mut().unwrap().1
             }
-            current_line = format!(
-                "{measurement}{tags_str},host={} {}={}",
-                hostname, field, metric.value
-            );
-            last_measurement = measurement;
-            last_tags = tags_str;
+        };
+        // A repeated field overwrites the earlier value, as it would on the server.
+        match fields.iter_mut().find(|(f, _)| *f == field) {
+            Some(existing) => existing.1 = value,
+            None => fields.push((field, value)),
         }
     }
 
-    // Push the last line
-    if !current_line.is_empty() {
-        lines.push(format!("{} {}", current_line, timestamp));
-    }
+    series
+        .iter()
+        .map(|((key, timestamp), fields)| {
+            let fields = fields
+                .iter()
+                .map(|(f, v)| format!("{}={}", f, v))
+                .collect::<Vec<_>>()
+                .join(",");
+            format!("{} {} {}", key, fields, timestamp)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
-    lines.join("\n")
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).expect("writing to a Vec cannot fail");
+    encoder.finish().expect("writing to a Vec cannot fail")
 }
 
 /// Exports metrics to InfluxDB.
@@ -68,15 +278,28 @@ pub async fn export_metrics(
     let mut request_builder = client.post(&config.url);
 
     // Add query parameters additively
+    // The v1 and v2 APIs spell sub-second precisions differently
     if let Some(db) = &config.db {
-        request_builder = request_builder.query(&[("db", db)]);
+        let precision = match config.precision {
+            Precision::S => "s",
+            Precision::Ms => "ms",
+            Precision::Us => "u",
+            Precision::Ns => "n",
+        };
+        request_builder = request_builder.query(&[("db", db.as_str()), ("precision", precision)]);
     }
 
     if let (Some(bucket), Some(org)) = (&config.bucket, &config.org) {
+        let precision = match config.precision {
+            Precision::S => "s",
+            Precision::Ms => "ms",
+            Precision::Us => "us",
+            Precision::Ns => "ns",
+        };
         request_builder = request_builder.query(&[
-            ("bucket", bucket),
-            ("org", org),
-            ("precision", &"s".to_string()),
+            ("bucket", bucket.as_str()),
+            ("org", org.as_str()),
+            ("precision", precision),
         ]);
     }
 
@@ -91,10 +314,19 @@ pub async fn export_metrics(
             request_builder = request_builder.basic_auth(username, Some(password));
         }
 
+    // Compress the body unless it is too small to be worth it
+    let body = if config.gzip && lines.len() >= config.gzip_min_size {
+        request_builder = request_builder.header("Content-Encoding", "gzip");
+        gzip(lines.as_bytes())
+    } else {
+        lines.as_bytes().to_vec()
+    };
+
     // Build and send the request
     request_builder
+        .timeout(Duration::from_secs(config.timeout))
         .header("Content-Type", "text/plain; charset=utf-8")
-        .body(lines.to_string())
+        .body(body)
         .send()
         .await?
         .error_for_status()?;
@@ -105,73 +337,354 @@ pub async fn export_metrics(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::collectors::Metric;
+    use crate::collectors::{Metric, Value};
+    use crate::config::{RetryConfig, SpoolConfig};
+    use crate::exporters::testutil::{deadline, http_server};
+
+    fn stamped(metrics: &[Metric], secs: u64) -> Vec<Metric> {
+        let timestamp = UNIX_EPOCH + Duration::from_secs(secs);
+        metrics
+            .iter()
+            .cloned()
+            .map(|mut m| {
+                m.timestamp = Some(timestamp);
+                m
+            })
+            .collect()
+    }
+
+    fn format_at(metrics: &[Metric], hostname: &str, secs: u64) -> String {
+        format_metrics(&stamped(metrics, secs), hostname, Precision::S)
+    }
+
+    fn influx_config(url: String) -> InfluxDBConfig {
+        InfluxDBConfig {
+            url,
+            bucket: None,
+            org: None,
+            token: None,
+            db: Some("test".to_string()),
+            username: None,
+            password: None,
+            spool: None,
+            timeout: 10,
+            gzip: false,
+            gzip_min_size: 1024,
+            max_batch_lines: 5000,
+            max_batch_bytes: 1024 * 1024,
+            retry: RetryConfig {
+                max_retries: 3,
+                initial_backoff: 1,
+                max_backoff: 10,
+            },
+            precision: Precision::S,
+        }
+    }
 
     #[test]
     fn test_format_metrics() {
         let metrics = vec![
-            Metric {
-                name: "cpu_usage".to_string(),
-                value: 0.5,
-                tags: vec![("core".to_string(), "cpu0".to_string())],
-            },
-            Metric {
-                name: "cpu_temperature".to_string(),
-                value: 60.0,
-                tags: vec![("core".to_string(), "cpu0".to_string())],
-            },
-            Metric {
-                name: "memory_total".to_string(),
-                value: 1024.0,
-                tags: vec![],
-            },
-            Metric {
-                name: "memory_used".to_string(),
-                value: 512.0,
-                tags: vec![],
-            },
+            Metric::new("cpu", "usage", Value::Float(0.5), vec![("core".to_string(), "cpu0".to_string())]),
+            Metric::new("cpu", "temperature", Value::Float(60.0), vec![("core".to_string(), "cpu0".to_string())]),
+            Metric::new("memory", "total", Value::Float(1024.0), vec![]),
+            Metric::new("memory", "used", Value::Float(512.0), vec![]),
         ];
 
-        let formatted = format_metrics(&metrics, "test-host", 1678886400);
+        let formatted = format_at(&metrics, "test-host", 1678886400);
         let expected = "cpu,core=cpu0,host=test-host usage=0.5,temperature=60 1678886400\nmemory,host=test-host total=1024,used=512 1678886400";
         assert_eq!(formatted, expected);
     }
 
     #[test]
-    fn test_format_metrics_no_grouping() {
+    fn test_format_metrics_global_grouping() {
         let metrics = vec![
-            Metric {
-                name: "cpu_usage".to_string(),
-                value: 0.5,
-                tags: vec![("core".to_string(), "cpu0".to_string())],
-            },
-            Metric {
-                name: "memory_total".to_string(),
-                value: 1024.0,
-                tags: vec![],
-            },
-            Metric {
-                name: "cpu_temperature".to_string(),
-                value: 60.0,
-                tags: vec![("core".to_string(), "cpu0".to_string())],
-            },
+            Metric::new("cpu", "usage", Value::Float(0.5), vec![("core".to_string(), "cpu0".to_string())]),
+            Metric::new("memory", "total", Value::Float(1024.0), vec![]),
+            Metric::new("cpu", "usage", Value::Float(0.7), vec![("core".to_string(), "cpu1".to_string())]),
+            Metric::new("cpu", "temperature", Value::Float(60.0), vec![("core".to_string(), "cpu0".to_string())]),
         ];
 
-        let formatted = format_metrics(&metrics, "test-host", 1678886400);
-        let expected = "cpu,core=cpu0,host=test-host usage=0.5 1678886400\nmemory,host=test-host total=1024 1678886400\ncpu,core=cpu0,host=test-host temperature=60 1678886400";
+        let formatted = format_at(&metrics, "test-host", 1678886400);
+        let expected = "cpu,core=cpu0,host=test-host usage=0.5,temperature=60 1678886400\nmemory,host=test-host total=1024 1678886400\ncpu,core=cpu1,host=test-host usage=0.7 1678886400";
+        assert_eq!(formatted, expected);
+    }
+
+    #[test]
+    fn test_format_metrics_tag_order() {
+        let metrics = vec![
+            Metric::new("network", "received", Value::UInt(1), vec![("interface".to_string(), "eth0".to_string())]),
+            Metric::new("disk", "reads", Value::UInt(2), vec![
+                ("device".to_string(), "sda".to_string()),
+                ("disk_id".to_string(), "wwn-1".to_string()),
+            ]),
+        ];
+
+        let formatted = format_at(&metrics, "test-host", 1678886400);
+        let expected = "network,host=test-host,interface=eth0 received=1u 1678886400\ndisk,device=sda,disk_id=wwn-1,host=test-host reads=2u 1678886400";
+        assert_eq!(formatted, expected);
+    }
+
+    #[test]
+    fn test_format_metrics_escaping() {
+        let metrics = vec![
+            Metric::new("my measurement,x", "field name=1", Value::Float(1.0), vec![
+                ("tag key".to_string(), "a,b=c d".to_string()),
+                ("empty".to_string(), "".to_string()),
+            ]),
+            Metric::new("my measurement,x", "nan", Value::Float(f64::NAN), vec![]),
+        ];
+
+        let formatted = format_at(&metrics, "my host", 1678886400);
+        let expected = r"my\ measurement\,x,host=my\ host,tag\ key=a\,b\=c\ d field\ name\=1=1 1678886400";
+        assert_eq!(formatted, expected);
+    }
+
+    #[test]
+    fn test_format_metrics_escaping_backslash() {
+        let metrics = vec![Metric::new("disk", "free", Value::UInt(1), vec![("device".to_string(), "C:\\".to_string())])];
+
+        let formatted = format_at(&metrics, "h", 1678886400);
+        assert_eq!(formatted, r"disk,device=C:\\,host=h free=1u 1678886400");
+    }
+
+    #[test]
+    fn test_format_metrics_multiline_string() {
+        let metrics = vec![
+            Metric::new("system", "motd", Value::String("line 1\nline 2".to_string()), vec![]),
+            Metric::new("system", "uptime", Value::UInt(1), vec![]),
+        ];
+
+        let formatted = format_at(&metrics, "h", 1678886400);
+        assert_eq!(formatted, r#"system,host=h motd="line 1 line 2",uptime=1u 1678886400"#);
+        assert_eq!(split_batches(&formatted, 1, 1024).len(), 1);
+
+        let metrics = vec![Metric::new("disk", "free", Value::UInt(1), vec![("label".to_string(), "a\nb\tc".to_string())])];
+        let formatted = format_at(&metrics, "h", 1678886400);
+        assert_eq!(formatted, r"disk,host=h,label=a\ b\ c free=1u 1678886400");
+    }
+
+    #[test]
+    fn test_format_metrics_explicit_field() {
+        let metrics = vec![
+            Metric::new("memory", "zswap_size", Value::UInt(0), vec![]),
+            Metric::new("system", "load_average_1m", Value::Float(0.25), vec![]),
+        ];
+
+        let formatted = format_at(&metrics, "test-host", 1678886400);
+        let expected = "memory,host=test-host zswap_size=0u 1678886400\nsystem,host=test-host load_average_1m=0.25 1678886400";
         assert_eq!(formatted, expected);
     }
 
     #[test]
     fn test_format_metrics_with_whitespace() {
-        let metrics = vec![Metric {
-            name: "cpu_usage".to_string(),
-            value: 0.5,
-            tags: vec![("core".to_string(), "cpu 0".to_string())],
-        }];
+        let metrics = vec![Metric::new("cpu", "usage", Value::Float(0.5), vec![("core".to_string(), "cpu 0".to_string())])];
 
-        let formatted = format_metrics(&metrics, "test-host", 1678886400);
+        let formatted = format_at(&metrics, "test-host", 1678886400);
         let expected = "cpu,core=cpu\\ 0,host=test-host usage=0.5 1678886400";
         assert_eq!(formatted, expected);
     }
+
+    #[tokio::test]
+    async fn test_export_replays_spool_in_order() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (url, requests) = http_server(vec![(503, ""), (204, "")]).await;
+        let mut config = influx_config(format!("{}/write", url));
+        config.retry.max_retries = 0;
+        config.spool = Some(SpoolConfig {
+            dir: temp_dir.path().to_str().unwrap().to_string(),
+            max_size: 1024 * 1024,
+            max_age: 3600,
+        });
+        let metrics = vec![Metric::new("memory", "used", Value::Float(512.0), vec![])];
+
+        let mut exporter = InfluxDBExporter::new(&Client::new(), &config, "test-host").unwrap();
+        assert!(exporter.export(&stamped(&metrics, 1), deadline()).await.is_err());
+        assert_eq!(exporter.spool.as_ref().unwrap().entries().unwrap().len(), 1);
+
+        // A restarted agent picks up the spooled batch and sends it before the new one.
+        let mut exporter = InfluxDBExporter::new(&Client::new(), &config, "test-host").unwrap();
+        exporter.export(&stamped(&metrics, 2), deadline()).await.unwrap();
+        assert!(exporter.spool.as_ref().unwrap().entries().unwrap().is_empty());
+
+        let bodies: Vec<String> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| String::from_utf8(r.body.clone()).unwrap())
+            .collect();
+        assert_eq!(
+            bodies,
+            vec![
+                "memory,host=test-host used=512 1",
+                "memory,host=test-host used=512 1",
+                "memory,host=test-host used=512 2",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_spools_from_first_failed_batch() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (url, requests) = http_server(vec![(204, ""), (503, "")]).await;
+        let mut config = influx_config(format!("{}/write", url));
+        config.max_batch_lines = 1;
+        config.retry.max_retries = 0;
+        config.spool = Some(SpoolConfig {
+            dir: temp_dir.path().to_str().unwrap().to_string(),
+            max_size: 1024 * 1024,
+            max_age: 3600,
+        });
+        let metrics = vec![
+            Metric::new("cpu", "usage", Value::Float(0.5), vec![]),
+            Metric::new("memory", "used", Value::Float(512.0), vec![]),
+            Metric::new("swap", "used", Value::Float(0.0), vec![]),
+        ];
+
+        let mut exporter = InfluxDBExporter::new(&Client::new(), &config, "test-host").unwrap();
+        match exporter.export(&stamped(&metrics, 1), deadline()).await {
+            Err(ExportError::Batches { total, failed }) => {
+                assert_eq!(total, 3);
+                assert_eq!(failed.len(), 1);
+                assert_eq!(failed[0].0, 1);
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        // The batch after the failed one is not sent but spooled behind it.
+        assert_eq!(requests.lock().unwrap().len(), 2);
+        let spooled: Vec<String> = exporter
+            .spool
+            .as_ref()
+            .unwrap()
+            .entries()
+            .unwrap()
+            .iter()
+            .map(|e| e.read().unwrap())
+            .collect();
+        assert_eq!(spooled, vec!["memory,host=test-host used=512 1", "swap,host=test-host used=0 1"]);
+    }
+
+    #[tokio::test]
+    async fn test_export_drops_rejected_batch() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (url, requests) = http_server(vec![(400, "")]).await;
+        let mut config = influx_config(format!("{}/write", url));
+        config.spool = Some(SpoolConfig {
+            dir: temp_dir.path().to_str().unwrap().to_string(),
+            max_size: 1024 * 1024,
+            max_age: 3600,
+        });
+        let metrics = vec![Metric::new("memory", "used", Value::Float(512.0), vec![])];
+
+        let mut exporter = InfluxDBExporter::new(&Client::new(), &config, "test-host").unwrap();
+        let error = exporter.export(&stamped(&metrics, 1), deadline()).await.unwrap_err();
+        assert!(matches!(error, ExportError::Batches { total: 1, ref failed } if failed.len() == 1));
+        assert!(exporter.spool.as_ref().unwrap().entries().unwrap().is_empty());
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].target, "/write?db=test&precision=s");
+        assert_eq!(requests[0].header("content-type"), Some("text/plain; charset=utf-8"));
+    }
+
+    #[tokio::test]
+    async fn test_export_retries_transient_failures() {
+        let (url, requests) = http_server(vec![(503, ""), (204, "")]).await;
+        let config = influx_config(format!("{}/write", url));
+        let metrics = vec![Metric::new("memory", "used", Value::Float(512.0), vec![])];
+
+        let mut exporter = InfluxDBExporter::new(&Client::new(), &config, "test-host").unwrap();
+        exporter.export(&stamped(&metrics, 1), deadline()).await.unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_export_metrics_gzip() {
+        let (url, requests) = http_server(vec![(204, "")]).await;
+        let mut config = influx_config(format!("{}/write", url));
+        config.gzip = true;
+        config.gzip_min_size = 16;
+        let client = Client::new();
+
+        export_metrics(&client, &config, "short").await.unwrap();
+        let long_lines = "memory,host=test-host used=512 1\n".repeat(4);
+        export_metrics(&client, &config, &long_lines).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].header("content-encoding"), None);
+        assert_eq!(requests[0].body, b"short");
+        assert_eq!(requests[1].header("content-encoding"), Some("gzip"));
+        let mut decoded = String::new();
+        io::Read::read_to_string(&mut flate2::read::GzDecoder::new(&requests[1].body[..]), &mut decoded).unwrap();
+        assert_eq!(decoded, long_lines);
+    }
+
+    #[test]
+    fn test_split_batches() {
+        let lines = "a 1\nb 2\nc 3\nd 4\ne 5";
+        assert_eq!(split_batches(lines, 2, 1024), vec!["a 1\nb 2", "c 3\nd 4", "e 5"]);
+        assert_eq!(split_batches(lines, 100, 8), vec!["a 1\nb 2", "c 3\nd 4", "e 5"]);
+        assert_eq!(split_batches("long line\na 1", 100, 4), vec!["long line", "a 1"]);
+        assert!(split_batches("", 100, 1024).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_export_reports_failed_batches() {
+        let (url, requests) = http_server(vec![(204, ""), (400, ""), (204, "")]).await;
+        let mut config = influx_config(format!("{}/write", url));
+        config.max_batch_lines = 1;
+        let metrics = vec![
+            Metric::new("cpu", "usage", Value::Float(0.5), vec![]),
+            Metric::new("memory", "used", Value::Float(512.0), vec![]),
+            Metric::new("swap", "used", Value::Float(0.0), vec![]),
+        ];
+
+        let mut exporter = InfluxDBExporter::new(&Client::new(), &config, "test-host").unwrap();
+        match exporter.export(&stamped(&metrics, 1), deadline()).await {
+            Err(ExportError::Batches { total, failed }) => {
+                assert_eq!(total, 3);
+                assert_eq!(failed.len(), 1);
+                assert_eq!(failed[0].0, 1);
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_format_metrics_typed_values() {
+        let metrics = vec![
+            Metric::new("disk", "read_bytes", Value::UInt(u64::MAX), vec![]),
+            Metric::new("disk", "offset", Value::Int(-3), vec![]),
+            Metric::new("disk", "healthy", Value::Bool(true), vec![]),
+            Metric::new("disk", "model", Value::String("Disk \"A\" \\ 1".to_string()), vec![]),
+        ];
+
+        let formatted = format_at(&metrics, "test-host", 1678886400);
+        let expected = "disk,host=test-host read_bytes=18446744073709551615u,offset=-3i,healthy=true,model=\"Disk \\\"A\\\" \\\\ 1\" 1678886400";
+        assert_eq!(formatted, expected);
+    }
+
+    #[test]
+    fn test_format_metrics_per_metric_timestamps() {
+        let mut metrics = vec![
+            Metric::new("cpu", "usage", Value::Float(0.5), vec![]),
+            Metric::new("cpu", "usage_user", Value::Float(0.25), vec![]),
+            Metric::new("cpu", "usage_system", Value::Float(0.25), vec![]),
+        ];
+        let sampled = UNIX_EPOCH + Duration::from_nanos(1_678_886_400_123_456_789);
+        metrics[0].timestamp = Some(sampled);
+        metrics[1].timestamp = Some(sampled + Duration::from_millis(500));
+        metrics[2].timestamp = Some(sampled);
+
+        let formatted = format_metrics(&metrics, "test-host", Precision::Ms);
+        let expected = "cpu,host=test-host usage=0.5,usage_system=0.25 1678886400123\ncpu,host=test-host usage_user=0.25 1678886400623";
+        assert_eq!(formatted, expected);
+
+        let formatted = format_metrics(&metrics[..1], "test-host", Precision::Ns);
+        assert_eq!(formatted, "cpu,host=test-host usage=0.5 1678886400123456789");
+        let formatted = format_metrics(&metrics[..1], "test-host", Precision::Us);
+        assert_eq!(formatted, "cpu,host=test-host usage=0.5 1678886400123456");
+    }
 }
@@ -0,0 +1,191 @@
+use super::rotate::RotatingFile;
+use super::{ExportError, Exporter};
+use crate::collectors::{Metric, Value};
+use crate::config::{JsonLinesConfig, JsonLinesMode};
+use async_trait::async_trait;
+use serde_json::{Map, json};
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+
+enum Output {
+    Stdout,
+    File(RotatingFile),
+}
+
+/// Writes metrics as JSON Lines to stdout or to a rotating file.
+pub struct JsonLinesExporter {
+    config: JsonLinesConfig,
+    hostname: String,
+    output: Output,
+}
+
+impl JsonLinesExporter {
+    pub fn new(config: &JsonLinesConfig, hostname: &str) -> io::Result<Self> {
+        let output = match &config.path {
+            Some(path) => Output::File(RotatingFile::open(path, config.rotate.as_ref())?),
+            None => Output::Stdout,
+        };
+        Ok(JsonLinesExporter {
+            config: config.clone(),
+            hostname: hostname.to_string(),
+            output,
+        })
+    }
+}
+
+#[async_trait]
+impl Exporter for JsonLinesExporter {
+    fn name(&self) -> &str {
+        "jsonl"
+    }
+
+    async fn export(&mut self, metrics: &[Metric], _deadline: Instant) -> Result<(), ExportError> {
+        let lines = format_metrics(metrics, &self.hostname, self.config.mode);
+        match &mut self.output {
+            Output::Stdout => {
+                let mut stdout = io::stdout().lock();
+                for line in &lines {
+                    stdout.write_all(line.as_bytes())?;
+                }
+                stdout.flush()?;
+            }
+            Output::File(file) => {
+                for line in &lines {
+                    file.write(line.as_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn unix_millis(timestamp: SystemTime) -> u64 {
+    timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+pub(crate) fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Int(v) => json!(v),
+        Value::UInt(v) => json!(v),
+        // JSON has no representation for NaN and infinity, so they become null.
+        Value::Float(v) => json!(v),
+        Value::Bool(v) => json!(v),
+        Value::String(v) => json!(v),
+    }
+}
+
+pub(crate) fn json_metric(metric: &Metric, now: SystemTime) -> serde_json::Value {
+    let tags: Map<String, serde_json::Value> = metric
+        .tags
+        .iter()
+        .map(|(k, v)| (k.clone(), json!(v)))
+        .collect();
+    json!({
+        "timestamp": unix_millis(metric.timestamp.unwrap_or(now)),
+        "measurement": metric.measurement,
+        "field": metric.field,
+        "value": json_value(&metric.value),
+        "tags": tags,
+    })
+}
+
+/// Formats metrics as newline terminated JSON objects, one per metric or one for the whole cycle.
+///
+/// Timestamps are milliseconds since the Unix epoch.
+fn format_metrics(metrics: &[Metric], hostname: &str, mode: JsonLinesMode) -> Vec<String> {
+    let now = SystemTime::now();
+    match mode {
+        JsonLinesMode::Metric => metrics
+            .iter()
+            .map(|metric| {
+                let mut record = json_metric(metric, now);
+                record["host"] = json!(hostname);
+                format!("{}\n", record)
+            })
+            .collect(),
+        JsonLinesMode::Cycle => {
+            if metrics.is_empty() {
+                return Vec::new();
+            }
+            let record = json!({
+                "timestamp": unix_millis(now),
+                "host": hostname,
+                "metrics": metrics.iter().map(|metric| json_metric(metric, now)).collect::<Vec<_>>(),
+            });
+            vec![format!("{}\n", record)]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporters::testutil::deadline;
+    use std::fs;
+    use std::time::Duration;
+
+    fn test_metrics() -> Vec<Metric> {
+        let mut metrics = vec![
+            Metric::new("cpu", "usage", Value::Float(0.5), vec![("core".to_string(), "cpu0".to_string())]),
+            Metric::new("disk", "model", Value::String("Disk A".to_string()), vec![]),
+            Metric::new("gpu", "power", Value::Float(f64::NAN), vec![]),
+        ];
+        for metric in &mut metrics {
+            metric.timestamp = Some(UNIX_EPOCH + Duration::from_millis(1678886400123));
+        }
+        metrics
+    }
+
+    #[test]
+    fn test_format_metrics_per_metric() {
+        let lines = format_metrics(&test_metrics(), "test-host", JsonLinesMode::Metric);
+        assert_eq!(lines.len(), 3);
+        let record: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(
+            record,
+            json!({
+                "timestamp": 1678886400123u64,
+                "measurement": "cpu",
+                "field": "usage",
+                "value": 0.5,
+                "tags": {"core": "cpu0"},
+                "host": "test-host",
+            })
+        );
+        let record: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+        assert_eq!(record["value"], json!("Disk A"));
+        let record: serde_json::Value = serde_json::from_str(&lines[2]).unwrap();
+        assert_eq!(record["value"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_format_metrics_per_cycle() {
+        let lines = format_metrics(&test_metrics(), "test-host", JsonLinesMode::Cycle);
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].ends_with('\n'));
+        let record: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(record["host"], json!("test-host"));
+        assert_eq!(record["metrics"].as_array().unwrap().len(), 3);
+        assert_eq!(record["metrics"][0]["tags"]["core"], json!("cpu0"));
+
+        assert!(format_metrics(&[], "test-host", JsonLinesMode::Cycle).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_export_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.jsonl");
+        let config = JsonLinesConfig {
+            path: Some(path.to_string_lossy().into_owned()),
+            mode: JsonLinesMode::Metric,
+            rotate: None,
+        };
+
+        let mut exporter = JsonLinesExporter::new(&config, "test-host").unwrap();
+        exporter.export(&test_metrics(), deadline()).await.unwrap();
+        exporter.export(&test_metrics(), deadline()).await.unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 6);
+    }
+}
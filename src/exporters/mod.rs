@@ -1 +1,268 @@
-pub mod influxdb;
\ No newline at end of file
+pub mod collectd;
+pub mod csv;
+pub mod elasticsearch;
+pub mod graphite;
+pub mod influxdb;
+pub mod jsonl;
+pub mod mqtt;
+pub mod otlp;
+pub mod parquet;
+pub mod prometheus;
+pub mod remote_write;
+pub mod retry;
+pub mod rotate;
+pub mod spool;
+pub mod sqlite;
+pub mod statsd;
+#[cfg(test)]
+pub mod testutil;
+pub mod webhook;
+pub mod zabbix;
+
+use crate::collectors::Metric;
+use crate::config;
+use async_trait::async_trait;
+use collectd::CollectdExporter;
+use csv::CsvExporter;
+use futures::future::join_all;
+use elasticsearch::ElasticsearchExporter;
+use graphite::GraphiteExporter;
+use influxdb::InfluxDBExporter;
+use jsonl::JsonLinesExporter;
+use mqtt::MqttExporter;
+use otlp::OtlpExporter;
+use prometheus::PrometheusExporter;
+use remote_write::RemoteWriteExporter;
+use reqwest::Client;
+use self::parquet::ParquetExporter;
+use sqlite::SqliteExporter;
+use statsd::StatsdExporter;
+use std::fmt;
+use std::io;
+use std::time::Duration;
+use tokio::time::{self, Instant};
+use webhook::WebhookExporter;
+use zabbix::ZabbixExporter;
+
+#[derive(Debug)]
+pub enum ExportError {
+    Http(reqwest::Error),
+    Io(io::Error),
+    Sqlite(rusqlite::Error),
+    Parquet(::parquet::errors::ParquetError),
+    /// Some batches of a split export failed. Holds the index and error of each failed batch.
+    Batches {
+        total: usize,
+        failed: Vec<(usize, ExportError)>,
+    },
+    /// The endpoint accepted the request but rejected some of its items. Holds the first reason.
+    Rejected {
+        failed: usize,
+        total: usize,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Http(e) => write!(f, "HTTP error: {}", e),
+            ExportError::Io(e) => write!(f, "I/O error: {}", e),
+            ExportError::Sqlite(e) => write!(f, "SQLite error: {}", e),
+            ExportError::Parquet(e) => write!(f, "Parquet error: {}", e),
+            ExportError::Batches { total, failed } => {
+                write!(f, "{} of {} batches failed", failed.len(), total)?;
+                for (index, e) in failed {
+                    write!(f, "; batch {}: {}", index + 1, e)?;
+                }
+                Ok(())
+            }
+            ExportError::Rejected { failed, total, reason } => {
+                write!(f, "{} of {} items rejected, first: {}", failed, total, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<reqwest::Error> for ExportError {
+    fn from(e: reqwest::Error) -> Self {
+        ExportError::Http(e)
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(e: io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for ExportError {
+    fn from(e: rusqlite::Error) -> Self {
+        ExportError::Sqlite(e)
+    }
+}
+
+impl From<::parquet::errors::ParquetError> for ExportError {
+    fn from(e: ::parquet::errors::ParquetError) -> Self {
+        ExportError::Parquet(e)
+    }
+}
+
+/// A destination for collected metrics, built from one `[[exporters]]` entry by [`build`].
+#[async_trait]
+pub trait Exporter: Send {
+    fn name(&self) -> &str;
+    /// Exports one collection cycle. The export is cancelled at `deadline`, so exporters that retry
+    /// must not start an attempt that cannot finish before it.
+    async fn export(&mut self, metrics: &[Metric], deadline: Instant) -> Result<(), ExportError>;
+    /// Writes out anything the exporter still buffers. Called once before the agent exits.
+    async fn flush(&mut self) -> Result<(), ExportError> {
+        Ok(())
+    }
+}
+
+/// Builds the exporter for one configuration entry.
+pub async fn build(config: &config::Exporter, client: &Client, hostname: &str) -> io::Result<Box<dyn Exporter>> {
+    Ok(match config {
+        config::Exporter::InfluxDB(influx_config) => Box::new(InfluxDBExporter::new(client, influx_config, hostname)?),
+        config::Exporter::Prometheus(prometheus_config) => {
+            Box::new(PrometheusExporter::bind(prometheus_config, hostname).await?)
+        }
+        config::Exporter::Otlp(otlp_config) => Box::new(OtlpExporter::new(client, otlp_config, hostname)),
+        config::Exporter::RemoteWrite(remote_write_config) => {
+            Box::new(RemoteWriteExporter::new(client, remote_write_config, hostname))
+        }
+        config::Exporter::Graphite(graphite_config) => Box::new(GraphiteExporter::new(graphite_config, hostname)),
+        config::Exporter::Statsd(statsd_config) => Box::new(StatsdExporter::connect(statsd_config, hostname).await?),
+        config::Exporter::JsonLines(jsonl_config) => Box::new(JsonLinesExporter::new(jsonl_config, hostname)?),
+        config::Exporter::Csv(csv_config) => Box::new(CsvExporter::new(csv_config)?),
+        config::Exporter::Mqtt(mqtt_config) => Box::new(MqttExporter::new(mqtt_config, hostname)?),
+        config::Exporter::Sqlite(sqlite_config) => {
+            Box::new(SqliteExporter::open(sqlite_config).map_err(io::Error::other)?)
+        }
+        config::Exporter::Parquet(parquet_config) => Box::new(ParquetExporter::new(parquet_config, hostname)?),
+        config::Exporter::Webhook(webhook_config) => Box::new(WebhookExporter::new(client, webhook_config, hostname)?),
+        config::Exporter::Elasticsearch(elasticsearch_config) => {
+            Box::new(ElasticsearchExporter::new(client, elasticsearch_config, hostname)?)
+        }
+        config::Exporter::Collectd(collectd_config) => {
+            Box::new(CollectdExporter::connect(collectd_config, hostname).await?)
+        }
+        config::Exporter::Zabbix(zabbix_config) => Box::new(ZabbixExporter::new(zabbix_config, hostname)),
+    })
+}
+
+/// The configured exporters, which the main loop hands every collection cycle to.
+pub struct Registry {
+    exporters: Vec<Box<dyn Exporter>>,
+}
+
+impl Registry {
+    pub async fn from_config<'a>(
+        configs: impl Iterator<Item = &'a config::Exporter>,
+        client: &Client,
+        hostname: &str,
+    ) -> io::Result<Self> {
+        let mut exporters = Vec::new();
+        for config in configs {
+            exporters.push(build(config, client, hostname).await?);
+        }
+        Ok(Registry { exporters })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exporters.is_empty()
+    }
+
+    /// Exports to every exporter concurrently, so a slow or failing one does not hold up the others.
+    /// Each export is bounded by `timeout`. Failures are logged.
+    pub async fn export(&mut self, metrics: &[Metric], timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let exports = self.exporters.iter_mut().map(|exporter| async {
+            let result = time::timeout_at(deadline, exporter.export(metrics, deadline)).await;
+            (exporter.name().to_string(), result)
+        });
+        for (name, result) in join_all(exports).await {
+            match result {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => eprintln!("[Error] Failed to export metrics to {}: {:#?}", name, e),
+                Err(_) => eprintln!("[Error] Timed out exporting metrics to {}", name),
+            }
+        }
+    }
+
+    /// Flushes every exporter. Failures are logged.
+    pub async fn flush(&mut self) {
+        for exporter in &mut self.exporters {
+            if let Err(e) = exporter.flush().await {
+                eprintln!("[Error] Failed to flush {}: {:#?}", exporter.name(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collectors::Value;
+    use std::sync::{Arc, Mutex};
+
+    /// Records every call, to check the registry without a real destination.
+    struct Recorder {
+        calls: Arc<Mutex<Vec<String>>>,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl Exporter for Recorder {
+        fn name(&self) -> &str {
+            "recorder"
+        }
+
+        async fn export(&mut self, metrics: &[Metric], _deadline: Instant) -> Result<(), ExportError> {
+            self.calls.lock().unwrap().push(format!("export {}", metrics.len()));
+            if self.fail {
+                return Err(io::Error::other("failed").into());
+            }
+            Ok(())
+        }
+
+        async fn flush(&mut self) -> Result<(), ExportError> {
+            self.calls.lock().unwrap().push("flush".to_string());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut registry = Registry {
+            exporters: vec![
+                Box::new(Recorder { calls: calls.clone(), fail: true }),
+                Box::new(Recorder { calls: calls.clone(), fail: false }),
+            ],
+        };
+        let metrics = vec![Metric::new("cpu", "usage", Value::Float(0.5), vec![])];
+
+        // A failing exporter does not stop the others.
+        registry.export(&metrics, Duration::from_secs(1)).await;
+        registry.flush().await;
+        assert_eq!(*calls.lock().unwrap(), vec!["export 1", "export 1", "flush", "flush"]);
+    }
+
+    #[tokio::test]
+    async fn test_from_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let config: crate::config::Config = toml::from_str(&format!(
+            "[[exporters]]\n[exporters.csv]\npath = {:?}\n[[exporters]]\n[exporters.zabbix]\n",
+            dir.path().join("metrics.csv")
+        ))
+        .unwrap();
+
+        let registry = Registry::from_config(config.exporters(), &Client::new(), "test-host").await.unwrap();
+        let names: Vec<&str> = registry.exporters.iter().map(|exporter| exporter.name()).collect();
+        assert_eq!(names, vec!["csv", "zabbix"]);
+    }
+}
@@ -0,0 +1,324 @@
+use super::{ExportError, Exporter};
+use crate::collectors::{Metric, MetricKind, Value};
+use crate::config::MqttConfig;
+use async_trait::async_trait;
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS, Transport};
+use serde_json::json;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::time::{self, Duration, Instant};
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const REQUEST_QUEUE_SIZE: usize = 1024;
+
+/// Publishes every metric as its own topic to an MQTT broker.
+pub struct MqttExporter {
+    config: MqttConfig,
+    hostname: String,
+    client: AsyncClient,
+    qos: QoS,
+    // Topics already announced to Home Assistant.
+    discovered: HashSet<String>,
+    // Set by the event loop on every connection. A publish is only queued by the client, and one
+    // that was queued when the connection dropped is lost, so discovery is repeated after a reconnect.
+    connected: Arc<AtomicBool>,
+}
+
+impl MqttExporter {
+    /// Sets up the client and starts its event loop, which connects and reconnects in the background.
+    pub fn new(config: &MqttConfig, hostname: &str) -> io::Result<Self> {
+        let qos = match config.qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            2 => QoS::ExactlyOnce,
+            qos => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid MQTT QoS {}", qos)));
+            }
+        };
+
+        let client_id = config.client_id.clone().unwrap_or_else(|| format!("rsysmetrics-{}", hostname));
+        let mut options = MqttOptions::new(client_id, &config.host, config.port);
+        options.set_keep_alive(KEEP_ALIVE);
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.clone().unwrap_or_default());
+        }
+        if let Some(tls) = &config.tls {
+            let client_auth = match (&tls.client_cert, &tls.client_key) {
+                (Some(cert), Some(key)) => Some((fs::read(cert)?, fs::read(key)?)),
+                (None, None) => None,
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "MQTT client_cert and client_key must be set together",
+                    ));
+                }
+            };
+            let transport = match (&tls.ca_file, client_auth) {
+                (Some(ca_file), client_auth) => Transport::tls(fs::read(ca_file)?, client_auth, None),
+                (None, None) => Transport::tls_with_default_config(),
+                (None, Some(_)) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "MQTT client_cert and client_key require ca_file",
+                    ));
+                }
+            };
+            options.set_transport(transport);
+        }
+
+        let (client, mut event_loop) = AsyncClient::new(options, REQUEST_QUEUE_SIZE);
+        let connected = Arc::new(AtomicBool::new(false));
+        let connected_loop = connected.clone();
+        tokio::spawn(async move {
+            loop {
+                match event_loop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => connected_loop.store(true, Ordering::Relaxed),
+                    Ok(Event::Incoming(_)) | Ok(Event::Outgoing(_)) => {}
+                    Err(e) => {
+                        // The next poll reconnects.
+                        eprintln!("[Error] MQTT connection failed, reconnecting in {:?}: {}", RECONNECT_DELAY, e);
+                        time::sleep(RECONNECT_DELAY).await;
+                    }
+                }
+            }
+        });
+
+        Ok(MqttExporter {
+            config: config.clone(),
+            hostname: hostname.to_string(),
+            client,
+            qos,
+            discovered: HashSet::new(),
+            connected,
+        })
+    }
+}
+
+#[async_trait]
+impl Exporter for MqttExporter {
+    fn name(&self) -> &str {
+        "mqtt"
+    }
+
+    async fn export(&mut self, metrics: &[Metric], _deadline: Instant) -> Result<(), ExportError> {
+        if self.connected.swap(false, Ordering::Relaxed) {
+            self.discovered.clear();
+        }
+        for metric in metrics {
+            let topic = format_topic(metric, &self.hostname, &self.config.topic);
+            if self.config.homeassistant && !self.discovered.contains(&topic) {
+                let (config_topic, payload) =
+                    discovery_config(metric, &self.hostname, &topic, &self.config.discovery_prefix);
+                self.client
+                    .publish(config_topic, QoS::AtLeastOnce, true, payload)
+                    .await
+                    .map_err(io::Error::other)?;
+                self.discovered.insert(topic.clone());
+            }
+            self.client
+                .publish(topic, self.qos, self.config.retain, format_value(&metric.value))
+                .await
+                .map_err(io::Error::other)?;
+        }
+        Ok(())
+    }
+}
+
+/// Replaces characters that would split a topic level or act as a wildcard.
+fn sanitize_level(level: &str) -> String {
+    level
+        .chars()
+        .map(|c| if c == '/' || c == '+' || c == '#' || c.is_control() { '_' } else { c })
+        .collect()
+}
+
+/// Expands the topic template for one metric.
+///
+/// `{host}`, `{name}`, `{measurement}` and `{field}` are replaced by the respective level,
+/// `{tag.KEY}` by the value of tag `KEY`, and `{tags}` by all tag values as separate levels.
+/// Placeholders without a value are left out of the topic.
+fn format_topic(metric: &Metric, hostname: &str, template: &str) -> String {
+    let mut topic = Vec::new();
+    for level in template.split('/') {
+        let expanded = match level {
+            "{host}" => sanitize_level(hostname),
+            "{name}" => sanitize_level(&metric.name()),
+            "{measurement}" => sanitize_level(&metric.measurement),
+            "{field}" => sanitize_level(&metric.field),
+            "{tags}" => metric
+                .tags
+                .iter()
+                .map(|(_, v)| sanitize_level(v))
+                .filter(|v| !v.is_empty())
+                .collect::<Vec<_>>()
+                .join("/"),
+            _ => match level.strip_prefix("{tag.").and_then(|s| s.strip_suffix('}')) {
+                Some(key) => metric
+                    .tags
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, v)| sanitize_level(v))
+                    .unwrap_or_default(),
+                None => level.to_string(),
+            },
+        };
+        if !expanded.is_empty() {
+            topic.push(expanded);
+        }
+    }
+    topic.join("/")
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::Int(v) => v.to_string(),
+        Value::UInt(v) => v.to_string(),
+        Value::Float(v) => v.to_string(),
+        Value::Bool(v) => v.to_string(),
+        Value::String(v) => v.clone(),
+    }
+}
+
+/// Reduces a string to the characters Home Assistant allows in node and object IDs.
+fn object_id(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect()
+}
+
+/// Builds the Home Assistant MQTT discovery topic and payload announcing `state_topic` as a sensor.
+fn discovery_config(metric: &Metric, hostname: &str, state_topic: &str, prefix: &str) -> (String, String) {
+    let node_id = object_id(hostname);
+    let unique_id = object_id(state_topic);
+    let name = std::iter::once(metric.name())
+        .chain(metric.tags.iter().map(|(_, v)| v.clone()))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut payload = json!({
+        "name": name,
+        "state_topic": state_topic,
+        "unique_id": unique_id,
+        "device": {
+            "identifiers": [format!("rsysmetrics_{}", node_id)],
+            "name": hostname,
+        },
+    });
+    // Home Assistant only accepts a state class for numeric states, bools are sent as "true"/"false".
+    if !matches!(metric.value, Value::String(_) | Value::Bool(_)) {
+        payload["state_class"] = json!(match metric.kind {
+            MetricKind::Gauge => "measurement",
+            MetricKind::Counter => "total_increasing",
+        });
+    }
+
+    (format!("{}/sensor/{}/{}/config", prefix, node_id, unique_id), payload.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MqttTlsConfig;
+    use crate::exporters::testutil::deadline;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn mqtt_config(port: u16) -> MqttConfig {
+        MqttConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            client_id: None,
+            username: None,
+            password: None,
+            tls: None,
+            topic: "rsysmetrics/{host}/{name}/{tags}".to_string(),
+            qos: 0,
+            retain: false,
+            homeassistant: false,
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+
+    fn cpu_metric() -> Metric {
+        Metric::new("cpu", "usage", Value::Float(0.5), vec![("core".to_string(), "cpu0".to_string())])
+    }
+
+    #[test]
+    fn test_format_topic() {
+        let metric = cpu_metric();
+        assert_eq!(format_topic(&metric, "host", "rsysmetrics/{host}/{name}/{tags}"), "rsysmetrics/host/cpu_usage/cpu0");
+        assert_eq!(format_topic(&metric, "host", "{host}/{measurement}/{tag.device}/{field}"), "host/cpu/usage");
+
+        let metric = Metric::new("disk", "used", Value::UInt(1), vec![("mount".to_string(), "/home".to_string())]);
+        assert_eq!(format_topic(&metric, "host", "{host}/{name}/{tags}"), "host/disk_used/_home");
+    }
+
+    #[test]
+    fn test_discovery_config() {
+        let metric = Metric::new("network", "received", Value::UInt(1), vec![("interface".to_string(), "eth0".to_string())])
+            .with_kind(MetricKind::Counter);
+        let (topic, payload) = discovery_config(&metric, "my.host", "rsysmetrics/my.host/network_received/eth0", "homeassistant");
+
+        assert_eq!(topic, "homeassistant/sensor/my_host/rsysmetrics_my_host_network_received_eth0/config");
+        let payload: serde_json::Value = serde_json::from_str(&payload).unwrap();
+        assert_eq!(payload["name"], json!("network_received eth0"));
+        assert_eq!(payload["state_topic"], json!("rsysmetrics/my.host/network_received/eth0"));
+        assert_eq!(payload["state_class"], json!("total_increasing"));
+        assert_eq!(payload["device"]["name"], json!("my.host"));
+
+        let metric = Metric::new("system", "docker_running", Value::Bool(true), vec![]);
+        let (_, payload) = discovery_config(&metric, "my.host", "rsysmetrics/my.host/system_docker_running", "homeassistant");
+        let payload: serde_json::Value = serde_json::from_str(&payload).unwrap();
+        assert!(payload.get("state_class").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_client_auth_config() {
+        let mut config = mqtt_config(1883);
+        config.tls = Some(MqttTlsConfig {
+            ca_file: None,
+            client_cert: Some("client.pem".to_string()),
+            client_key: Some("client.key".to_string()),
+        });
+        assert!(MqttExporter::new(&config, "host").is_err());
+
+        config.tls = Some(MqttTlsConfig {
+            ca_file: Some("ca.pem".to_string()),
+            client_cert: Some("client.pem".to_string()),
+            client_key: None,
+        });
+        let error = MqttExporter::new(&config, "host").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        // A minimal broker that accepts the connection and records the publish packets.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = mqtt_config(listener.local_addr().unwrap().port());
+
+        let mut exporter = MqttExporter::new(&config, "host").unwrap();
+        exporter.export(&[cpu_metric()], deadline()).await.unwrap();
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buf = vec![0u8; 1024];
+        let n = stream.read(&mut buf).await.unwrap();
+        assert_eq!(buf[0], 0x10, "expected CONNECT");
+        assert!(buf[..n].windows(15).any(|w| w == b"rsysmetrics-hos"));
+        stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap();
+
+        let mut received = Vec::new();
+        while !received.ends_with(b"0.5") {
+            let n = stream.read(&mut buf).await.unwrap();
+            assert!(n > 0);
+            received.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(received[0], 0x30, "expected PUBLISH");
+        assert!(received.windows(29).any(|w| w == b"rsysmetrics/host/cpu_usage/cp"));
+    }
+}
@@ -0,0 +1,390 @@
+use super::retry::with_retry;
+use super::{ExportError, Exporter};
+use crate::collectors::{Metric, MetricKind, Value};
+use crate::config::OtlpConfig;
+use async_trait::async_trait;
+use prost::Message;
+use reqwest::Client;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sysinfo::System;
+use tokio::time::Instant;
+
+/// The subset of the OTLP metrics protocol (opentelemetry/proto v1) used by the exporter.
+mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ExportMetricsServiceRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub resource_metrics: Vec<ResourceMetrics>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ExportMetricsServiceResponse {
+        #[prost(message, optional, tag = "1")]
+        pub partial_success: Option<ExportMetricsPartialSuccess>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ExportMetricsPartialSuccess {
+        #[prost(int64, tag = "1")]
+        pub rejected_data_points: i64,
+        #[prost(string, tag = "2")]
+        pub error_message: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ResourceMetrics {
+        #[prost(message, optional, tag = "1")]
+        pub resource: Option<Resource>,
+        #[prost(message, repeated, tag = "2")]
+        pub scope_metrics: Vec<ScopeMetrics>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Resource {
+        #[prost(message, repeated, tag = "1")]
+        pub attributes: Vec<KeyValue>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ScopeMetrics {
+        #[prost(message, optional, tag = "1")]
+        pub scope: Option<InstrumentationScope>,
+        #[prost(message, repeated, tag = "2")]
+        pub metrics: Vec<Metric>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct InstrumentationScope {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, tag = "2")]
+        pub version: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Metric {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(oneof = "MetricData", tags = "5, 7")]
+        pub data: Option<MetricData>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum MetricData {
+        #[prost(message, tag = "5")]
+        Gauge(Gauge),
+        #[prost(message, tag = "7")]
+        Sum(Sum),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Gauge {
+        #[prost(message, repeated, tag = "1")]
+        pub data_points: Vec<NumberDataPoint>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Sum {
+        #[prost(message, repeated, tag = "1")]
+        pub data_points: Vec<NumberDataPoint>,
+        #[prost(int32, tag = "2")]
+        pub aggregation_temporality: i32,
+        #[prost(bool, tag = "3")]
+        pub is_monotonic: bool,
+    }
+
+    pub const AGGREGATION_TEMPORALITY_CUMULATIVE: i32 = 2;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct NumberDataPoint {
+        #[prost(message, repeated, tag = "7")]
+        pub attributes: Vec<KeyValue>,
+        #[prost(fixed64, tag = "2")]
+        pub start_time_unix_nano: u64,
+        #[prost(fixed64, tag = "3")]
+        pub time_unix_nano: u64,
+        #[prost(oneof = "NumberValue", tags = "4, 6")]
+        pub value: Option<NumberValue>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum NumberValue {
+        #[prost(double, tag = "4")]
+        AsDouble(f64),
+        #[prost(sfixed64, tag = "6")]
+        AsInt(i64),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct KeyValue {
+        #[prost(string, tag = "1")]
+        pub key: String,
+        #[prost(message, optional, tag = "2")]
+        pub value: Option<AnyValue>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct AnyValue {
+        #[prost(oneof = "any_value::Value", tags = "1")]
+        pub value: Option<any_value::Value>,
+    }
+
+    pub mod any_value {
+        #[derive(Clone, PartialEq, prost::Oneof)]
+        pub enum Value {
+            #[prost(string, tag = "1")]
+            StringValue(String),
+        }
+    }
+
+    pub fn string_attribute(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue {
+                value: Some(any_value::Value::StringValue(value.to_string())),
+            }),
+        }
+    }
+}
+
+/// Sends metrics to an OpenTelemetry collector over OTLP/HTTP with protobuf encoding.
+pub struct OtlpExporter {
+    client: Client,
+    config: OtlpConfig,
+    hostname: String,
+    // Start of the cumulative sums reported for counters. The collected counters are kernel
+    // counters, which count from boot.
+    start_time: SystemTime,
+}
+
+impl OtlpExporter {
+    pub fn new(client: &Client, config: &OtlpConfig, hostname: &str) -> Self {
+        OtlpExporter {
+            client: client.clone(),
+            config: config.clone(),
+            hostname: hostname.to_string(),
+            start_time: match System::boot_time() {
+                0 => SystemTime::now(),
+                boot_time => UNIX_EPOCH + Duration::from_secs(boot_time),
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl Exporter for OtlpExporter {
+    fn name(&self) -> &str {
+        "otlp"
+    }
+
+    async fn export(&mut self, metrics: &[Metric], deadline: Instant) -> Result<(), ExportError> {
+        let request = build_request(metrics, &self.hostname, &self.config, self.start_time);
+        if request.resource_metrics[0].scope_metrics[0].metrics.is_empty() {
+            return Ok(());
+        }
+        let body = request.encode_to_vec();
+
+        let response = with_retry(&self.config.retry, deadline, || async {
+            let mut request_builder = self
+                .client
+                .post(&self.config.endpoint)
+                .timeout(Duration::from_secs(self.config.timeout))
+                .header("Content-Type", "application/x-protobuf");
+            for (name, value) in &self.config.headers {
+                request_builder = request_builder.header(name, value);
+            }
+            request_builder.body(body.clone()).send().await?.error_for_status()?.bytes().await
+        })
+        .await?;
+
+        // The collector reports points it dropped in an otherwise successful response
+        if let Ok(response) = proto::ExportMetricsServiceResponse::decode(response)
+            && let Some(partial) = response.partial_success
+            && partial.rejected_data_points > 0
+        {
+            eprintln!(
+                "[Warning] OTLP endpoint rejected {} data points: {}",
+                partial.rejected_data_points, partial.error_message
+            );
+        }
+        Ok(())
+    }
+}
+
+fn unix_nanos(timestamp: SystemTime) -> u64 {
+    timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64
+}
+
+/// Converts a metric value to an OTLP number. Strings have no numeric representation.
+fn number_value(value: &Value) -> Option<proto::NumberValue> {
+    match value {
+        Value::Int(v) => Some(proto::NumberValue::AsInt(*v)),
+        Value::UInt(v) => Some(match i64::try_from(*v) {
+            Ok(v) => proto::NumberValue::AsInt(v),
+            Err(_) => proto::NumberValue::AsDouble(*v as f64),
+        }),
+        Value::Float(v) => Some(proto::NumberValue::AsDouble(*v)),
+        Value::Bool(v) => Some(proto::NumberValue::AsInt(*v as i64)),
+        Value::String(_) => None,
+    }
+}
+
+/// Builds an OTLP export request with one metric per name, holding a data point for each tag set.
+///
+/// Counters become monotonic cumulative sums starting at `start_time`; everything else is a gauge.
+fn build_request(
+    metrics: &[Metric],
+    hostname: &str,
+    config: &OtlpConfig,
+    start_time: SystemTime,
+) -> proto::ExportMetricsServiceRequest {
+    let now = SystemTime::now();
+    let mut otlp_metrics: Vec<proto::Metric> = Vec::new();
+
+    for metric in metrics {
+        let Some(value) = number_value(&metric.value) else {
+            continue;
+        };
+        let data_point = proto::NumberDataPoint {
+            attributes: metric
+                .tags
+                .iter()
+                .map(|(k, v)| proto::string_attribute(k, v))
+                .collect(),
+            start_time_unix_nano: match metric.kind {
+                MetricKind::Counter => unix_nanos(start_time),
+                MetricKind::Gauge => 0,
+            },
+            time_unix_nano: unix_nanos(metric.timestamp.unwrap_or(now)),
+            value: Some(value),
+        };
+
+        let name = metric.name();
+        let index = match otlp_metrics.iter().position(|m| m.name == name) {
+            Some(index) => index,
+            None => {
+                let data = match metric.kind {
+                    MetricKind::Gauge => proto::MetricData::Gauge(proto::Gauge::default()),
+                    MetricKind::Counter => proto::MetricData::Sum(proto::Sum {
+                        data_points: Vec::new(),
+                        aggregation_temporality: proto::AGGREGATION_TEMPORALITY_CUMULATIVE,
+                        is_monotonic: true,
+                    }),
+                };
+                otlp_metrics.push(proto::Metric { name, data: Some(data) });
+                otlp_metrics.len() - 1
+            }
+        };
+        match otlp_metrics[index].data.as_mut() {
+            Some(proto::MetricData::Gauge(gauge)) => gauge.data_points.push(data_point),
+            Some(proto::MetricData::Sum(sum)) => sum.data_points.push(data_point),
+            None => {}
+        }
+    }
+
+    let mut attributes = vec![proto::string_attribute("host.name", hostname)];
+    attributes.extend(
+        config
+            .resource_attributes
+            .iter()
+            .filter(|(k, _)| *k != "host.name")
+            .map(|(k, v)| proto::string_attribute(k, v)),
+    );
+
+    proto::ExportMetricsServiceRequest {
+        resource_metrics: vec![proto::ResourceMetrics {
+            resource: Some(proto::Resource { attributes }),
+            scope_metrics: vec![proto::ScopeMetrics {
+                scope: Some(proto::InstrumentationScope {
+                    name: env!("CARGO_PKG_NAME").to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                }),
+                metrics: otlp_metrics,
+            }],
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RetryConfig;
+    use crate::exporters::testutil::{deadline, http_server};
+    use std::collections::BTreeMap;
+
+    fn otlp_config(endpoint: String) -> OtlpConfig {
+        OtlpConfig {
+            endpoint,
+            headers: BTreeMap::from([("X-Api-Key".to_string(), "secret".to_string())]),
+            resource_attributes: BTreeMap::from([("service.name".to_string(), "rsysmetrics".to_string())]),
+            timeout: 10,
+            retry: RetryConfig::default(),
+        }
+    }
+
+    fn test_metrics() -> Vec<Metric> {
+        let mut metrics = vec![
+            Metric::new("cpu", "usage", Value::Float(0.5), vec![("core".to_string(), "cpu0".to_string())]),
+            Metric::new("cpu", "usage", Value::Float(1.5), vec![("core".to_string(), "cpu1".to_string())]),
+            Metric::new("disk", "read_bytes", Value::UInt(4096), vec![("device".to_string(), "sda".to_string())])
+                .with_kind(MetricKind::Counter),
+            Metric::new("disk", "model", Value::String("Disk A".to_string()), vec![]),
+        ];
+        for metric in &mut metrics {
+            metric.timestamp = Some(UNIX_EPOCH + Duration::from_secs(1678886400));
+        }
+        metrics
+    }
+
+    #[test]
+    fn test_build_request() {
+        let start_time = UNIX_EPOCH + Duration::from_secs(1678880000);
+        let request = build_request(&test_metrics(), "test-host", &otlp_config(String::new()), start_time);
+
+        let resource_metrics = &request.resource_metrics[0];
+        assert_eq!(
+            resource_metrics.resource.as_ref().unwrap().attributes,
+            vec![
+                proto::string_attribute("host.name", "test-host"),
+                proto::string_attribute("service.name", "rsysmetrics"),
+            ]
+        );
+
+        let metrics = &resource_metrics.scope_metrics[0].metrics;
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics[0].name, "cpu_usage");
+        let Some(proto::MetricData::Gauge(gauge)) = &metrics[0].data else {
+            panic!("cpu_usage should be a gauge");
+        };
+        assert_eq!(gauge.data_points.len(), 2);
+        assert_eq!(gauge.data_points[1].attributes, vec![proto::string_attribute("core", "cpu1")]);
+        assert_eq!(gauge.data_points[1].time_unix_nano, 1678886400 * 1_000_000_000);
+        assert_eq!(gauge.data_points[1].value, Some(proto::NumberValue::AsDouble(1.5)));
+
+        assert_eq!(metrics[1].name, "disk_read_bytes");
+        let Some(proto::MetricData::Sum(sum)) = &metrics[1].data else {
+            panic!("disk_read_bytes should be a sum");
+        };
+        assert!(sum.is_monotonic);
+        assert_eq!(sum.aggregation_temporality, proto::AGGREGATION_TEMPORALITY_CUMULATIVE);
+        assert_eq!(sum.data_points[0].start_time_unix_nano, 1678880000 * 1_000_000_000);
+        assert_eq!(sum.data_points[0].value, Some(proto::NumberValue::AsInt(4096)));
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let (url, requests) = http_server(vec![(200, "")]).await;
+        let config = otlp_config(format!("{}/v1/metrics", url));
+
+        let mut exporter = OtlpExporter::new(&Client::new(), &config, "test-host");
+        exporter.export(&test_metrics(), deadline()).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].target, "/v1/metrics");
+        assert_eq!(requests[0].header("content-type"), Some("application/x-protobuf"));
+        assert_eq!(requests[0].header("x-api-key"), Some("secret"));
+        let request = proto::ExportMetricsServiceRequest::decode(&requests[0].body[..]).unwrap();
+        assert_eq!(request.resource_metrics[0].scope_metrics[0].metrics.len(), 2);
+    }
+}
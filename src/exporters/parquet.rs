@@ -0,0 +1,492 @@
+use super::csv::column_name;
+use super::sqlite::format_tags;
+use super::{ExportError, Exporter};
+use crate::collectors::Metric;
+use crate::config::{ParquetConfig, ParquetSchema};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use parquet::basic::{Compression, ConvertedType, Repetition, Type as PhysicalType};
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type};
+use parquet::errors::Result;
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::mem;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+
+const MILLIS_PER_HOUR: i64 = 60 * 60 * 1000;
+
+/// A Parquet file being written. It only becomes readable once its footer is written on close,
+/// so it is kept under a hidden temporary name until then.
+struct OpenFile {
+    writer: SerializedFileWriter<File>,
+    hour: i64,
+    tmp_path: PathBuf,
+    path: PathBuf,
+}
+
+/// A row of the long schema, waiting for the next row group.
+struct LongRow {
+    timestamp: i64,
+    name: String,
+    measurement: String,
+    field: String,
+    tags: String,
+    value: f64,
+}
+
+/// A row of the wide schema, with a value or null for every series known when it was collected.
+struct WideRow {
+    timestamp: i64,
+    values: Vec<Option<f64>>,
+}
+
+/// Writes metrics to hourly Parquet files.
+///
+/// The long schema has one row per metric: `timestamp`, `host`, `name`, `measurement`, `field`,
+/// `tags` (a JSON object) and `value`. The wide schema has one row per cycle with `timestamp`,
+/// `host` and a column per series seen in the hour, named like `cpu_usage{core=cpu0}`. A series
+/// that shows up mid-hour in the wide schema starts a new file. String values are not written.
+///
+/// Rows are buffered and written as one row group once `row_group_rows` or `row_group_bytes` is
+/// reached, once the oldest row has waited `row_group_interval` seconds, and when the file is closed.
+pub struct ParquetExporter {
+    config: ParquetConfig,
+    hostname: String,
+    file: Option<OpenFile>,
+    columns: Vec<String>,
+    indices: HashMap<String, usize>,
+    long_rows: Vec<LongRow>,
+    wide_rows: Vec<WideRow>,
+    buffered_bytes: usize,
+    buffered_since: Option<Instant>,
+}
+
+impl ParquetExporter {
+    pub fn new(config: &ParquetConfig, hostname: &str) -> std::io::Result<Self> {
+        fs::create_dir_all(&config.dir)?;
+        // A file left open by a crash has no footer and cannot be read, so it is removed.
+        let prefix = format!(".{}-", file_prefix(hostname));
+        for entry in fs::read_dir(&config.dir)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if name.starts_with(&prefix) && name.ends_with(".parquet.tmp") {
+                eprintln!("[Warning] Removing incomplete Parquet file {}", path.display());
+                fs::remove_file(&path)?;
+            }
+        }
+        Ok(ParquetExporter {
+            config: config.clone(),
+            hostname: hostname.to_string(),
+            file: None,
+            columns: Vec::new(),
+            indices: HashMap::new(),
+            long_rows: Vec::new(),
+            wide_rows: Vec::new(),
+            buffered_bytes: 0,
+            buffered_since: None,
+        })
+    }
+
+    /// Writes the buffered rows to the current file as a row group.
+    fn write_row_group(&mut self) -> Result<()> {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+        // The buffer is emptied even if the write fails, the same rows would fail again.
+        self.buffered_bytes = 0;
+        self.buffered_since = None;
+        match self.config.schema {
+            ParquetSchema::Long if !self.long_rows.is_empty() => {
+                write_long(&mut file.writer, &mem::take(&mut self.long_rows), &self.hostname)
+            }
+            ParquetSchema::Wide if !self.wide_rows.is_empty() => {
+                write_wide(&mut file.writer, &mem::take(&mut self.wide_rows), &self.hostname)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the buffered rows, finishes the current file and moves it to its final name.
+    fn close(&mut self) -> Result<()> {
+        self.write_row_group()?;
+        if let Some(file) = self.file.take() {
+            file.writer.close()?;
+            fs::rename(&file.tmp_path, &file.path)?;
+        }
+        Ok(())
+    }
+
+    fn open(&self, cycle_time: SystemTime, hour: i64) -> Result<OpenFile> {
+        // Files of the same hour, from a restart or a schema change, get a numbered suffix.
+        let stem = format!(
+            "{}-{}",
+            file_prefix(&self.hostname),
+            DateTime::<Utc>::from(cycle_time).format("%Y%m%d%H")
+        );
+        let dir = PathBuf::from(&self.config.dir);
+        let name = (0..)
+            .map(|n| match n {
+                0 => format!("{}.parquet", stem),
+                n => format!("{}-{}.parquet", stem, n),
+            })
+            .find(|name| !dir.join(name).exists())
+            .unwrap();
+
+        let tmp_path = dir.join(format!(".{}.tmp", name));
+        let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+        let writer = SerializedFileWriter::new(File::create(&tmp_path)?, self.schema()?, Arc::new(properties))?;
+        Ok(OpenFile {
+            writer,
+            hour,
+            tmp_path,
+            path: dir.join(name),
+        })
+    }
+
+    fn schema(&self) -> Result<Arc<Type>> {
+        let mut fields = vec![
+            Type::primitive_type_builder("timestamp", PhysicalType::INT64)
+                .with_repetition(Repetition::REQUIRED)
+                .with_converted_type(ConvertedType::TIMESTAMP_MILLIS)
+                .build()?,
+            string_column("host")?,
+        ];
+        match self.config.schema {
+            ParquetSchema::Long => {
+                for name in ["name", "measurement", "field", "tags"] {
+                    fields.push(string_column(name)?);
+                }
+                fields.push(double_column("value")?);
+            }
+            ParquetSchema::Wide => {
+                for column in &self.columns {
+                    fields.push(double_column(column)?);
+                }
+            }
+        }
+        let fields = fields.into_iter().map(Arc::new).collect();
+        Ok(Arc::new(Type::group_type_builder("rsysmetrics").with_fields(fields).build()?))
+    }
+}
+
+#[async_trait]
+impl Exporter for ParquetExporter {
+    fn name(&self) -> &str {
+        "parquet"
+    }
+
+    async fn export(&mut self, metrics: &[Metric], _deadline: Instant) -> Result<(), ExportError> {
+        let metrics: Vec<&Metric> = metrics.iter().filter(|m| m.value.as_f64().is_some()).collect();
+        if metrics.is_empty() {
+            return Ok(());
+        }
+
+        let now = SystemTime::now();
+        let cycle_time = metrics.iter().filter_map(|m| m.timestamp).min().unwrap_or(now);
+        let hour = unix_millis(cycle_time).div_euclid(MILLIS_PER_HOUR);
+
+        if self.file.as_ref().is_some_and(|file| file.hour != hour) {
+            self.close()?;
+            // The columns are collected per hour, so series that went away are not carried on forever.
+            self.columns.clear();
+            self.indices.clear();
+        }
+
+        let mut new_series = false;
+        if self.config.schema == ParquetSchema::Wide {
+            for metric in &metrics {
+                let column = column_name(metric);
+                if !self.indices.contains_key(&column) {
+                    self.indices.insert(column.clone(), self.columns.len());
+                    self.columns.push(column);
+                    new_series = true;
+                }
+            }
+        }
+
+        if new_series {
+            self.close()?;
+        }
+        if self.file.is_none() {
+            self.file = Some(self.open(cycle_time, hour)?);
+        }
+
+        let buffered_since = *self.buffered_since.get_or_insert_with(Instant::now);
+        let rows = match self.config.schema {
+            ParquetSchema::Long => {
+                for metric in &metrics {
+                    let row = LongRow {
+                        timestamp: unix_millis(metric.timestamp.unwrap_or(now)),
+                        name: metric.name(),
+                        measurement: metric.measurement.clone(),
+                        field: metric.field.clone(),
+                        tags: format_tags(&metric.tags),
+                        value: metric.value.as_f64().unwrap_or_default(),
+                    };
+                    self.buffered_bytes +=
+                        16 + self.hostname.len() + row.name.len() + row.measurement.len() + row.field.len() + row.tags.len();
+                    self.long_rows.push(row);
+                }
+                self.long_rows.len()
+            }
+            ParquetSchema::Wide => {
+                let mut values = vec![None; self.columns.len()];
+                for metric in &metrics {
+                    values[self.indices[&column_name(metric)]] = metric.value.as_f64();
+                }
+                self.buffered_bytes += 8 + self.hostname.len() + 8 * values.len();
+                self.wide_rows.push(WideRow {
+                    timestamp: unix_millis(cycle_time),
+                    values,
+                });
+                self.wide_rows.len()
+            }
+        };
+        if rows >= self.config.row_group_rows
+            || self.buffered_bytes >= self.config.row_group_bytes
+            || buffered_since.elapsed() >= Duration::from_secs(self.config.row_group_interval)
+        {
+            self.write_row_group()?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), ExportError> {
+        Ok(self.close()?)
+    }
+}
+
+/// The hostname as used in file names.
+fn file_prefix(hostname: &str) -> String {
+    hostname.replace(['/', '\\'], "_")
+}
+
+fn string_column(name: &str) -> Result<Type> {
+    Type::primitive_type_builder(name, PhysicalType::BYTE_ARRAY)
+        .with_repetition(Repetition::REQUIRED)
+        .with_converted_type(ConvertedType::UTF8)
+        .build()
+}
+
+fn double_column(name: &str) -> Result<Type> {
+    Type::primitive_type_builder(name, PhysicalType::DOUBLE)
+        .with_repetition(Repetition::OPTIONAL)
+        .build()
+}
+
+fn unix_millis(timestamp: SystemTime) -> i64 {
+    timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64
+}
+
+/// Writes a row group with one row per metric.
+fn write_long(writer: &mut SerializedFileWriter<File>, rows: &[LongRow], hostname: &str) -> Result<()> {
+    let timestamps: Vec<i64> = rows.iter().map(|row| row.timestamp).collect();
+    let values: Vec<f64> = rows.iter().map(|row| row.value).collect();
+    let strings = |f: fn(&LongRow) -> &str| -> Vec<ByteArray> { rows.iter().map(|row| f(row).into()).collect() };
+    let string_columns = [
+        rows.iter().map(|_| hostname.into()).collect(),
+        strings(|row| &row.name),
+        strings(|row| &row.measurement),
+        strings(|row| &row.field),
+        strings(|row| &row.tags),
+    ];
+
+    let mut row_group = writer.next_row_group()?;
+    let mut index = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        match index {
+            0 => {
+                column.typed::<Int64Type>().write_batch(&timestamps, None, None)?;
+            }
+            1..=5 => {
+                column.typed::<ByteArrayType>().write_batch(&string_columns[index - 1], None, None)?;
+            }
+            _ => {
+                column.typed::<DoubleType>().write_batch(&values, Some(&vec![1; values.len()]), None)?;
+            }
+        }
+        column.close()?;
+        index += 1;
+    }
+    row_group.close()?;
+    Ok(())
+}
+
+/// Writes a row group with one row per cycle, holding the value of every series in the file, or null.
+fn write_wide(writer: &mut SerializedFileWriter<File>, rows: &[WideRow], hostname: &str) -> Result<()> {
+    let timestamps: Vec<i64> = rows.iter().map(|row| row.timestamp).collect();
+    let hosts: Vec<ByteArray> = rows.iter().map(|_| hostname.into()).collect();
+
+    let mut row_group = writer.next_row_group()?;
+    let mut index = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        match index {
+            0 => {
+                column.typed::<Int64Type>().write_batch(&timestamps, None, None)?;
+            }
+            1 => {
+                column.typed::<ByteArrayType>().write_batch(&hosts, None, None)?;
+            }
+            _ => {
+                let values: Vec<Option<f64>> = rows.iter().map(|row| row.values[index - 2]).collect();
+                let levels: Vec<i16> = values.iter().map(|value| value.is_some() as i16).collect();
+                let values: Vec<f64> = values.into_iter().flatten().collect();
+                column.typed::<DoubleType>().write_batch(&values, Some(&levels), None)?;
+            }
+        }
+        column.close()?;
+        index += 1;
+    }
+    row_group.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporters::testutil::deadline;
+    use crate::collectors::Value;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::{Field, Row};
+
+    fn stamped(mut metrics: Vec<Metric>, secs: u64) -> Vec<Metric> {
+        for metric in &mut metrics {
+            metric.timestamp = Some(UNIX_EPOCH + Duration::from_secs(secs));
+        }
+        metrics
+    }
+
+    fn cpu(core: &str, value: f64) -> Metric {
+        Metric::new("cpu", "usage", Value::Float(value), vec![("core".to_string(), core.to_string())])
+    }
+
+    fn read_rows(path: PathBuf) -> Vec<Row> {
+        let reader = SerializedFileReader::new(File::open(path).unwrap()).unwrap();
+        reader.get_row_iter(None).unwrap().map(|row| row.unwrap()).collect()
+    }
+
+    fn field<'a>(row: &'a Row, name: &str) -> &'a Field {
+        row.get_column_iter().find(|(n, _)| *n == name).map(|(_, f)| f).unwrap()
+    }
+
+    fn parquet_config(dir: &tempfile::TempDir, schema: ParquetSchema) -> ParquetConfig {
+        ParquetConfig {
+            dir: dir.path().to_string_lossy().into_owned(),
+            schema,
+            row_group_rows: 100_000,
+            row_group_bytes: 16 * 1024 * 1024,
+            row_group_interval: 3600,
+        }
+    }
+
+    fn row_groups(path: PathBuf) -> usize {
+        SerializedFileReader::new(File::open(path).unwrap()).unwrap().metadata().num_row_groups()
+    }
+
+    // 2023-03-15 13:20:00 UTC
+    const TIME: u64 = 1678886400;
+
+    #[tokio::test]
+    async fn test_export_long() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut exporter = ParquetExporter::new(&parquet_config(&dir, ParquetSchema::Long), "host").unwrap();
+
+        let metrics = vec![cpu("cpu0", 0.5), Metric::new("disk", "model", Value::String("Disk A".to_string()), vec![])];
+        exporter.export(&stamped(metrics, TIME), deadline()).await.unwrap();
+        exporter.export(&stamped(vec![cpu("cpu0", 1.5)], TIME + 15), deadline()).await.unwrap();
+        // The next hour closes the first file.
+        exporter.export(&stamped(vec![cpu("cpu0", 2.5)], TIME + 3600), deadline()).await.unwrap();
+
+        let rows = read_rows(dir.path().join("host-2023031513.parquet"));
+        assert_eq!(rows.len(), 2);
+        assert_eq!(field(&rows[0], "name"), &Field::Str("cpu_usage".to_string()));
+        assert_eq!(field(&rows[0], "tags"), &Field::Str(r#"{"core":"cpu0"}"#.to_string()));
+        assert_eq!(field(&rows[0], "value"), &Field::Double(0.5));
+        assert_eq!(field(&rows[1], "timestamp"), &Field::TimestampMillis((TIME as i64 + 15) * 1000));
+        // Both cycles were buffered and written together.
+        assert_eq!(row_groups(dir.path().join("host-2023031513.parquet")), 1);
+        assert!(dir.path().join(".host-2023031514.parquet.tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn test_row_group_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ParquetConfig {
+            row_group_rows: 2,
+            ..parquet_config(&dir, ParquetSchema::Wide)
+        };
+        let mut exporter = ParquetExporter::new(&config, "host").unwrap();
+
+        for i in 0..3 {
+            exporter.export(&stamped(vec![cpu("cpu0", i as f64)], TIME + i * 15), deadline()).await.unwrap();
+        }
+        exporter.flush().await.unwrap();
+
+        let path = dir.path().join("host-2023031513.parquet");
+        assert_eq!(row_groups(path.clone()), 2);
+        let rows = read_rows(path);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(field(&rows[2], "cpu_usage{core=cpu0}"), &Field::Double(2.0));
+    }
+
+    #[tokio::test]
+    async fn test_row_group_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ParquetConfig {
+            row_group_interval: 0,
+            ..parquet_config(&dir, ParquetSchema::Long)
+        };
+        let mut exporter = ParquetExporter::new(&config, "host").unwrap();
+
+        for i in 0..3 {
+            exporter.export(&stamped(vec![cpu("cpu0", i as f64)], TIME + i * 15), deadline()).await.unwrap();
+        }
+        exporter.flush().await.unwrap();
+
+        assert_eq!(row_groups(dir.path().join("host-2023031513.parquet")), 3);
+    }
+
+    #[tokio::test]
+    async fn test_removes_incomplete_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(".host-2023031513.parquet.tmp"), b"PAR1").unwrap();
+        fs::write(dir.path().join(".other-2023031513.parquet.tmp"), b"PAR1").unwrap();
+
+        ParquetExporter::new(&parquet_config(&dir, ParquetSchema::Long), "host").unwrap();
+        assert!(!dir.path().join(".host-2023031513.parquet.tmp").exists());
+        // Files of another host sharing the directory are left alone.
+        assert!(dir.path().join(".other-2023031513.parquet.tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn test_export_wide() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut exporter = ParquetExporter::new(&parquet_config(&dir, ParquetSchema::Wide), "host").unwrap();
+
+        exporter.export(&stamped(vec![cpu("cpu0", 0.5)], TIME), deadline()).await.unwrap();
+        exporter.export(&stamped(vec![cpu("cpu1", 1.5)], TIME + 15), deadline()).await.unwrap();
+        exporter.export(&stamped(vec![cpu("cpu1", 2.5)], TIME + 3600), deadline()).await.unwrap();
+        exporter.flush().await.unwrap();
+
+        let rows = read_rows(dir.path().join("host-2023031513.parquet"));
+        assert_eq!(rows.len(), 1);
+        assert_eq!(field(&rows[0], "cpu_usage{core=cpu0}"), &Field::Double(0.5));
+
+        // The new series started a second file for the same hour.
+        let rows = read_rows(dir.path().join("host-2023031513-1.parquet"));
+        assert_eq!(field(&rows[0], "cpu_usage{core=cpu0}"), &Field::Null);
+        assert_eq!(field(&rows[0], "cpu_usage{core=cpu1}"), &Field::Double(1.5));
+        assert_eq!(field(&rows[0], "host"), &Field::Str("host".to_string()));
+
+        // The next hour only has columns for the series seen in it.
+        let rows = read_rows(dir.path().join("host-2023031514.parquet"));
+        let columns: Vec<&str> = rows[0].get_column_iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(columns, vec!["timestamp", "host", "cpu_usage{core=cpu1}"]);
+    }
+}
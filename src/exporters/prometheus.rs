@@ -0,0 +1,263 @@
+use super::{ExportError, Exporter};
+use crate::collectors::{Metric, MetricKind};
+use crate::config::PrometheusConfig;
+use async_trait::async_trait;
+use std::io;
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{self, Duration, Instant};
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+const MAX_REQUEST_SIZE: usize = 8192;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serves the most recently collected metrics in the Prometheus text exposition format.
+pub struct PrometheusExporter {
+    hostname: String,
+    body: Arc<RwLock<String>>,
+}
+
+impl PrometheusExporter {
+    /// Binds the listen address and starts serving scrape requests in the background.
+    pub async fn bind(config: &PrometheusConfig, hostname: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(&config.listen).await?;
+        let body = Arc::new(RwLock::new(String::new()));
+        tokio::spawn(serve(listener, config.path.clone(), body.clone()));
+
+        Ok(PrometheusExporter {
+            hostname: hostname.to_string(),
+            body,
+        })
+    }
+
+    /// Replaces the metrics returned to subsequent scrapes.
+    pub fn update(&self, metrics: &[Metric]) {
+        let body = format_metrics(metrics, &self.hostname);
+        *self.body.write().unwrap() = body;
+    }
+}
+
+#[async_trait]
+impl Exporter for PrometheusExporter {
+    fn name(&self) -> &str {
+        "prometheus"
+    }
+
+    async fn export(&mut self, metrics: &[Metric], _deadline: Instant) -> Result<(), ExportError> {
+        self.update(metrics);
+        Ok(())
+    }
+}
+
+async fn serve(listener: TcpListener, path: String, body: Arc<RwLock<String>>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let path = path.clone();
+                let body = body.clone();
+                tokio::spawn(async move {
+                    match time::timeout(REQUEST_TIMEOUT, handle_connection(stream, &path, &body)).await {
+                        Ok(Err(e)) => eprintln!("[Error] Failed to serve scrape request: {}", e),
+                        Err(_) => eprintln!("[Error] Scrape request timed out"),
+                        Ok(Ok(())) => {}
+                    }
+                });
+            }
+            Err(e) => {
+                eprintln!("[Error] Failed to accept scrape connection: {}", e);
+            }
+        }
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, path: &str, body: &RwLock<String>) -> io::Result<()> {
+    // Only the request line matters, but the headers are drained so the client sees a clean close.
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_SIZE {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.lines().next().unwrap_or_default().split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let target = target.split('?').next().unwrap_or_default();
+
+    let response = if method != "GET" && method != "HEAD" {
+        response("405 Method Not Allowed", "text/plain", "Method Not Allowed\n", method == "HEAD")
+    } else if target != path {
+        response("404 Not Found", "text/plain", "Not Found\n", method == "HEAD")
+    } else {
+        let body = body.read().unwrap();
+        response("200 OK", CONTENT_TYPE, &body, method == "HEAD")
+    };
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn response(status: &str, content_type: &str, body: &str, head_only: bool) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        if head_only { "" } else { body }
+    )
+}
+
+/// Replaces characters that are not allowed in Prometheus metric and label names.
+pub(crate) fn sanitize_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == ':' { c } else { '_' })
+        .collect();
+    if sanitized.is_empty() || sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+/// Escapes special characters in Prometheus label values.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Returns the metric family name, with the `_total` suffix that counters are named with by convention.
+pub(crate) fn family_name(metric: &Metric) -> String {
+    let name = sanitize_name(&metric.name());
+    match metric.kind {
+        MetricKind::Counter if !name.ends_with("_total") => format!("{}_total", name),
+        _ => name,
+    }
+}
+
+/// Formats a slice of metrics into the Prometheus text exposition format.
+pub fn format_metrics(metrics: &[Metric], hostname: &str) -> String {
+    // Samples of one metric family must be contiguous, so group by name in order of first appearance.
+    let mut families: Vec<(String, MetricKind, Vec<String>)> = Vec::new();
+
+    for metric in metrics {
+        // Prometheus samples are numeric, so string values cannot be exposed.
+        let Some(value) = metric.value.as_f64() else {
+            continue;
+        };
+        let name = family_name(metric);
+
+        let mut tags = metric.tags.clone();
+        tags.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut labels = tags
+            .iter()
+            .filter(|(k, _)| k != "host")
+            .map(|(k, v)| format!("{}=\"{}\"", sanitize_name(k), escape_label_value(v)))
+            .collect::<Vec<_>>();
+        labels.push(format!("host=\"{}\"", escape_label_value(hostname)));
+
+        let sample = format!("{}{{{}}} {}", name, labels.join(","), format_value(value));
+        match families.iter_mut().find(|(family, _, _)| *family == name) {
+            Some((_, _, samples)) => samples.push(sample),
+            None => families.push((name, metric.kind, vec![sample])),
+        }
+    }
+
+    let mut output = String::new();
+    for (name, kind, samples) in families {
+        let kind = match kind {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+        };
+        output.push_str(&format!("# TYPE {} {}\n", name, kind));
+        for sample in samples {
+            output.push_str(&sample);
+            output.push('\n');
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collectors::Value;
+
+    #[test]
+    fn test_format_metrics() {
+        let metrics = vec![
+            Metric::new("cpu", "usage", Value::Float(0.5), vec![("core".to_string(), "cpu0".to_string())]),
+            Metric::new("memory", "total", Value::UInt(1024), vec![]),
+            Metric::new("disk", "model", Value::String("Disk A".to_string()), vec![]),
+            Metric::new("cpu", "usage", Value::Float(1.5), vec![("core".to_string(), "cpu1".to_string())]),
+            Metric::new("network", "received", Value::UInt(4096), vec![("interface".to_string(), "eth0".to_string())])
+                .with_kind(MetricKind::Counter),
+        ];
+
+        let formatted = format_metrics(&metrics, "test-host");
+        let expected = "# TYPE cpu_usage gauge\n\
+            cpu_usage{core=\"cpu0\",host=\"test-host\"} 0.5\n\
+            cpu_usage{core=\"cpu1\",host=\"test-host\"} 1.5\n\
+            # TYPE memory_total gauge\n\
+            memory_total{host=\"test-host\"} 1024\n\
+            # TYPE network_received_total counter\n\
+            network_received_total{interface=\"eth0\",host=\"test-host\"} 4096\n";
+        assert_eq!(formatted, expected);
+    }
+
+    #[test]
+    fn test_format_metrics_escaping() {
+        let metrics = vec![Metric::new("gpu", "temperature_edge-1", Value::Float(f64::NAN), vec![("label".to_string(), "a \"b\"\\c".to_string())])];
+
+        let formatted = format_metrics(&metrics, "test-host");
+        let expected = "# TYPE gpu_temperature_edge_1 gauge\n\
+            gpu_temperature_edge_1{label=\"a \\\"b\\\"\\\\c\",host=\"test-host\"} NaN\n";
+        assert_eq!(formatted, expected);
+    }
+
+    #[test]
+    fn test_sanitize_name() {
+        assert_eq!(sanitize_name("cpu_usage"), "cpu_usage");
+        assert_eq!(sanitize_name("disk.read bytes"), "disk_read_bytes");
+        assert_eq!(sanitize_name("1m"), "_1m");
+    }
+
+    #[tokio::test]
+    async fn test_serve_metrics() {
+        let config = PrometheusConfig {
+            listen: "127.0.0.1:0".to_string(),
+            path: "/metrics".to_string(),
+        };
+        let listener = TcpListener::bind(&config.listen).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let body = Arc::new(RwLock::new("up 1\n".to_string()));
+        tokio::spawn(serve(listener, config.path.clone(), body));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\nup 1\n"));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET /other HTTP/1.1\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
@@ -0,0 +1,221 @@
+use super::prometheus::{family_name, sanitize_name};
+use super::retry::with_retry;
+use super::{ExportError, Exporter};
+use crate::collectors::Metric;
+use crate::config::RemoteWriteConfig;
+use async_trait::async_trait;
+use prost::Message;
+use reqwest::Client;
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+
+/// The Prometheus remote_write 1.0 messages (prometheus/prompb).
+mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct WriteRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub timeseries: Vec<TimeSeries>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TimeSeries {
+        #[prost(message, repeated, tag = "1")]
+        pub labels: Vec<Label>,
+        #[prost(message, repeated, tag = "2")]
+        pub samples: Vec<Sample>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Label {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, tag = "2")]
+        pub value: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Sample {
+        #[prost(double, tag = "1")]
+        pub value: f64,
+        /// Milliseconds since the Unix epoch.
+        #[prost(int64, tag = "2")]
+        pub timestamp: i64,
+    }
+}
+
+/// Pushes metrics to a Prometheus remote_write endpoint such as Mimir, Thanos or VictoriaMetrics.
+pub struct RemoteWriteExporter {
+    client: Client,
+    config: RemoteWriteConfig,
+    hostname: String,
+}
+
+impl RemoteWriteExporter {
+    pub fn new(client: &Client, config: &RemoteWriteConfig, hostname: &str) -> Self {
+        RemoteWriteExporter {
+            client: client.clone(),
+            config: config.clone(),
+            hostname: hostname.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Exporter for RemoteWriteExporter {
+    fn name(&self) -> &str {
+        "remote_write"
+    }
+
+    async fn export(&mut self, metrics: &[Metric], deadline: Instant) -> Result<(), ExportError> {
+        let request = build_request(metrics, &self.hostname);
+        if request.timeseries.is_empty() {
+            return Ok(());
+        }
+        let body = snap::raw::Encoder::new()
+            .compress_vec(&request.encode_to_vec())
+            .map_err(io::Error::other)?;
+
+        with_retry(&self.config.retry, deadline, || async {
+            let mut request_builder = self
+                .client
+                .post(&self.config.url)
+                .timeout(Duration::from_secs(self.config.timeout))
+                .header("Content-Type", "application/x-protobuf")
+                .header("Content-Encoding", "snappy")
+                .header("X-Prometheus-Remote-Write-Version", "0.1.0");
+            if let Some(username) = &self.config.username {
+                request_builder = request_builder.basic_auth(username, self.config.password.as_ref());
+            }
+            for (name, value) in &self.config.headers {
+                request_builder = request_builder.header(name, value);
+            }
+            request_builder.body(body.clone()).send().await?.error_for_status()
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+/// Builds a write request with one time series per metric, labelled the same way as the scrape endpoint.
+fn build_request(metrics: &[Metric], hostname: &str) -> proto::WriteRequest {
+    let now = SystemTime::now();
+    let mut timeseries = Vec::new();
+
+    for metric in metrics {
+        // Prometheus samples are numeric, so string values cannot be sent.
+        let Some(value) = metric.value.as_f64() else {
+            continue;
+        };
+
+        // Remote write receivers expect the labels sorted by name.
+        let mut labels: Vec<proto::Label> = metric
+            .tags
+            .iter()
+            .filter(|(k, _)| k != "host")
+            .map(|(k, v)| proto::Label {
+                name: sanitize_name(k),
+                value: v.clone(),
+            })
+            .collect();
+        labels.push(proto::Label {
+            name: "__name__".to_string(),
+            value: family_name(metric),
+        });
+        labels.push(proto::Label {
+            name: "host".to_string(),
+            value: hostname.to_string(),
+        });
+        labels.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let timestamp = metric.timestamp.unwrap_or(now);
+        timeseries.push(proto::TimeSeries {
+            labels,
+            samples: vec![proto::Sample {
+                value,
+                timestamp: timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64,
+            }],
+        });
+    }
+
+    proto::WriteRequest { timeseries }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collectors::{MetricKind, Value};
+    use crate::config::RetryConfig;
+    use crate::exporters::testutil::{deadline, http_server};
+    use std::collections::BTreeMap;
+
+    fn label(name: &str, value: &str) -> proto::Label {
+        proto::Label {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    fn test_metrics() -> Vec<Metric> {
+        let mut metrics = vec![
+            Metric::new("cpu", "usage", Value::Float(0.5), vec![("core".to_string(), "cpu0".to_string())]),
+            Metric::new("disk", "model", Value::String("Disk A".to_string()), vec![]),
+            Metric::new("network", "received", Value::UInt(2048), vec![("interface".to_string(), "eth0".to_string())])
+                .with_kind(MetricKind::Counter),
+        ];
+        for metric in &mut metrics {
+            metric.timestamp = Some(UNIX_EPOCH + Duration::from_millis(1678886400123));
+        }
+        metrics
+    }
+
+    #[test]
+    fn test_build_request() {
+        let request = build_request(&test_metrics(), "test-host");
+
+        assert_eq!(request.timeseries.len(), 2);
+        assert_eq!(
+            request.timeseries[0].labels,
+            vec![label("__name__", "cpu_usage"), label("core", "cpu0"), label("host", "test-host")]
+        );
+        assert_eq!(
+            request.timeseries[0].samples,
+            vec![proto::Sample {
+                value: 0.5,
+                timestamp: 1678886400123
+            }]
+        );
+        assert_eq!(
+            request.timeseries[1].labels,
+            vec![label("__name__", "network_received_total"), label("host", "test-host"), label("interface", "eth0")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let (url, requests) = http_server(vec![(204, "")]).await;
+        let config = RemoteWriteConfig {
+            url: format!("{}/api/v1/push", url),
+            username: Some("user".to_string()),
+            password: Some("pass".to_string()),
+            headers: BTreeMap::from([("X-Scope-OrgID".to_string(), "tenant".to_string())]),
+            timeout: 10,
+            retry: RetryConfig::default(),
+        };
+
+        let mut exporter = RemoteWriteExporter::new(&Client::new(), &config, "test-host");
+        exporter.export(&test_metrics(), deadline()).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].target, "/api/v1/push");
+        assert_eq!(requests[0].header("content-encoding"), Some("snappy"));
+        assert_eq!(requests[0].header("x-prometheus-remote-write-version"), Some("0.1.0"));
+        assert_eq!(requests[0].header("x-scope-orgid"), Some("tenant"));
+        assert_eq!(requests[0].header("authorization"), Some("Basic dXNlcjpwYXNz"));
+
+        let body = snap::raw::Decoder::new().decompress_vec(&requests[0].body).unwrap();
+        let request = proto::WriteRequest::decode(&body[..]).unwrap();
+        assert_eq!(request, build_request(&test_metrics(), "test-host"));
+    }
+}
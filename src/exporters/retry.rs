@@ -0,0 +1,142 @@
+use crate::config::RetryConfig;
+use reqwest::StatusCode;
+use std::future::Future;
+use tokio::time::{self, Duration, Instant};
+
+/// Returns true if a request that failed with `error` may succeed when sent again.
+///
+/// Timeouts, connection failures, `408`, `429` and `5xx` responses are transient. Any other
+/// client error means the server rejected the request itself, so retrying cannot help.
+pub fn is_transient(error: &reqwest::Error) -> bool {
+    match error.status() {
+        Some(status) => {
+            status.is_server_error()
+                || status == StatusCode::REQUEST_TIMEOUT
+                || status == StatusCode::TOO_MANY_REQUESTS
+        }
+        None => error.is_timeout() || error.is_connect() || error.is_request(),
+    }
+}
+
+/// Returns the delay before retry number `attempt` (starting at 0).
+///
+/// The delay grows exponentially up to `max_backoff`, and a random jitter of up to half the
+/// delay keeps agents that failed at the same time from retrying in lockstep.
+pub(crate) fn backoff(config: &RetryConfig, attempt: u32) -> Duration {
+    let delay = config
+        .initial_backoff
+        .saturating_mul(1u64 << attempt.min(32))
+        .min(config.max_backoff);
+    let jitter = fastrand::u64(0..=delay / 2);
+    Duration::from_millis(delay - jitter)
+}
+
+/// Runs `op` until it succeeds, fails permanently, or the retries are exhausted.
+///
+/// The export is cancelled at `deadline`, so no retry is started that could not finish before it,
+/// judging by how long the failed attempt took.
+pub async fn with_retry<T, F, Fut>(config: &RetryConfig, deadline: Instant, mut op: F) -> Result<T, reqwest::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, reqwest::Error>>,
+{
+    let mut attempt = 0;
+    loop {
+        let started = Instant::now();
+        match op().await {
+            Err(e) if attempt < config.max_retries && is_transient(&e) => {
+                let delay = backoff(config, attempt);
+                if Instant::now() + delay + started.elapsed() > deadline {
+                    eprintln!("[Warning] Request failed, no time left to retry before the export deadline: {}", e);
+                    return Err(e);
+                }
+                eprintln!("[Warning] Request failed, retrying in {:?}: {}", delay, e);
+                time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporters::testutil::{deadline, http_server};
+    use reqwest::Client;
+
+    fn retry_config(max_retries: u32) -> RetryConfig {
+        RetryConfig {
+            max_retries,
+            initial_backoff: 1,
+            max_backoff: 10,
+        }
+    }
+
+    async fn post(client: &Client, url: &str) -> Result<(), reqwest::Error> {
+        client.post(url).send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_backoff() {
+        let config = RetryConfig {
+            max_retries: 10,
+            initial_backoff: 100,
+            max_backoff: 1000,
+        };
+        for _ in 0..100 {
+            let first = backoff(&config, 0);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            let capped = backoff(&config, 8);
+            assert!(capped >= Duration::from_millis(500) && capped <= Duration::from_millis(1000));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_errors() {
+        let (url, requests) = http_server(vec![(503, ""), (429, ""), (204, "")]).await;
+        let client = Client::new();
+
+        with_retry(&retry_config(3), deadline(), || post(&client, &url)).await.unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_retries() {
+        let (url, requests) = http_server(vec![(500, "")]).await;
+        let client = Client::new();
+
+        let error = with_retry(&retry_config(2), deadline(), || post(&client, &url)).await.unwrap_err();
+        assert_eq!(error.status(), Some(StatusCode::INTERNAL_SERVER_ERROR));
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_stops_retrying_at_deadline() {
+        let (url, requests) = http_server(vec![(500, "")]).await;
+        let client = Client::new();
+        let config = RetryConfig {
+            max_retries: 10,
+            initial_backoff: 100,
+            max_backoff: 100,
+        };
+
+        let deadline = Instant::now() + Duration::from_millis(400);
+        let error = with_retry(&config, deadline, || post(&client, &url)).await.unwrap_err();
+        assert_eq!(error.status(), Some(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(Instant::now() <= deadline);
+        let requests = requests.lock().unwrap().len();
+        assert!((2..=8).contains(&requests), "{} requests", requests);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_permanent_errors() {
+        let (url, requests) = http_server(vec![(401, ""), (204, "")]).await;
+        let client = Client::new();
+
+        let error = with_retry(&retry_config(3), deadline(), || post(&client, &url)).await.unwrap_err();
+        assert!(!is_transient(&error));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::config::RotateConfig;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// An append-only output file that is rotated once it grows too large or too old.
+///
+/// Rotated files are renamed to `<path>.1`, `<path>.2`, ... with `.1` being the most recent,
+/// and files beyond `max_files` are deleted. Without a rotation config the file is only rotated
+/// on request and every rotated file is kept.
+pub struct RotatingFile {
+    path: PathBuf,
+    rotate: Option<RotateConfig>,
+    file: File,
+    size: u64,
+    opened: SystemTime,
+}
+
+impl RotatingFile {
+    /// Opens `path` for appending, creating it and its parent directory if needed.
+    pub fn open(path: impl AsRef<Path>, rotate: Option<&RotateConfig>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata = file.metadata()?;
+        Ok(RotatingFile {
+            path,
+            rotate: rotate.cloned(),
+            file,
+            size: metadata.len(),
+            // The age of a file that already existed counts from its creation where the platform records it.
+            opened: metadata.created().unwrap_or_else(|_| SystemTime::now()),
+        })
+    }
+
+    /// Returns true if nothing has been written to the current file yet.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Rotates the file first if writing `len` more bytes would exceed the size limit, or if it is too old.
+    ///
+    /// A file that is still empty is never rotated.
+    pub fn rotate_if_needed(&mut self, len: usize) -> io::Result<()> {
+        let Some(rotate) = &self.rotate else {
+            return Ok(());
+        };
+        if self.size == 0 {
+            return Ok(());
+        }
+        let too_large = rotate.max_size.is_some_and(|max| self.size + len as u64 > max);
+        let too_old = rotate.max_age.is_some_and(|max| {
+            self.opened.elapsed().unwrap_or_default() >= Duration::from_secs(max)
+        });
+        if too_large || too_old {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    /// Moves the current file aside and continues in a new, empty one.
+    pub fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        let max_files = match &self.rotate {
+            Some(rotate) => rotate.max_files,
+            None => (1..).find(|n| !rotated(*n).exists()).unwrap_or(1),
+        };
+        if max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            match fs::remove_file(rotated(max_files)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            for n in (1..max_files).rev() {
+                match fs::rename(rotated(n), rotated(n + 1)) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            fs::rename(&self.path, rotated(1))?;
+        }
+
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        self.opened = SystemTime::now();
+        Ok(())
+    }
+
+    /// Appends `data`, rotating the file beforehand if needed.
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.rotate_if_needed(data.len())?;
+        self.file.write_all(data)?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.log");
+        let rotate = RotateConfig {
+            max_size: Some(10),
+            max_age: None,
+            max_files: 2,
+        };
+
+        let mut file = RotatingFile::open(&path, Some(&rotate)).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth\n");
+        assert_eq!(fs::read_to_string(dir.path().join("out.log.1")).unwrap(), "third\n");
+        assert_eq!(fs::read_to_string(dir.path().join("out.log.2")).unwrap(), "second\n");
+        assert!(!dir.path().join("out.log.3").exists());
+    }
+
+    #[test]
+    fn test_rotate_by_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.log");
+        let rotate = RotateConfig {
+            max_size: None,
+            max_age: Some(0),
+            max_files: 1,
+        };
+
+        let mut file = RotatingFile::open(&path, Some(&rotate)).unwrap();
+        file.write(b"first\n").unwrap();
+        file.write(b"second\n").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second\n");
+        assert_eq!(fs::read_to_string(dir.path().join("out.log.1")).unwrap(), "first\n");
+    }
+
+    #[test]
+    fn test_no_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.log");
+
+        let mut file = RotatingFile::open(&path, None).unwrap();
+        file.write(b"first\n").unwrap();
+        let mut file = RotatingFile::open(&path, None).unwrap();
+        file.write(b"second\n").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first\nsecond\n");
+    }
+
+    #[test]
+    fn test_rotate_on_request_keeps_all_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.log");
+
+        let mut file = RotatingFile::open(&path, None).unwrap();
+        for line in ["first\n", "second\n", "third\n"] {
+            file.write(line.as_bytes()).unwrap();
+            file.rotate().unwrap();
+        }
+
+        assert!(file.is_empty());
+        assert_eq!(fs::read_to_string(dir.path().join("out.log.1")).unwrap(), "third\n");
+        assert_eq!(fs::read_to_string(dir.path().join("out.log.3")).unwrap(), "first\n");
+    }
+}
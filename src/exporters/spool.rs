@@ -0,0 +1,181 @@
+use crate::config::SpoolConfig;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const EXTENSION: &str = "lp";
+
+/// A batch stored in the spool, identified by its sequence number and creation time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpoolEntry {
+    pub seq: u64,
+    pub created: u64,
+    path: PathBuf,
+}
+
+impl SpoolEntry {
+    pub fn read(&self) -> io::Result<String> {
+        fs::read_to_string(&self.path)
+    }
+}
+
+/// A bounded on-disk queue of line protocol batches that could not be delivered yet.
+///
+/// Every batch is a separate file named `<seq>-<created>.lp`, so the queue order and the
+/// age of each batch survive restarts without any additional index.
+pub struct Spool {
+    dir: PathBuf,
+    max_size: u64,
+    max_age: u64,
+    next_seq: u64,
+}
+
+impl Spool {
+    pub fn open(config: &SpoolConfig) -> io::Result<Self> {
+        fs::create_dir_all(&config.dir)?;
+        let mut spool = Spool {
+            dir: PathBuf::from(&config.dir),
+            max_size: config.max_size,
+            max_age: config.max_age,
+            next_seq: 0,
+        };
+        spool.next_seq = spool.scan()?.last().map_or(0, |e| e.seq + 1);
+        Ok(spool)
+    }
+
+    /// Appends a batch to the end of the queue, evicting the oldest batches if the size limit is exceeded.
+    pub fn push(&mut self, batch: &str) -> io::Result<()> {
+        let name = format!("{:020}-{}.{}", self.next_seq, now(), EXTENSION);
+        // Write to a temporary name first so a crash never leaves a truncated batch in the queue.
+        let tmp_path = self.dir.join(format!(".{}.tmp", name));
+        fs::write(&tmp_path, batch)?;
+        fs::rename(&tmp_path, self.dir.join(name))?;
+        self.next_seq += 1;
+
+        let mut entries = self.entries()?;
+        let mut total_size = entries
+            .iter()
+            .map(|e| fs::metadata(&e.path).map_or(0, |m| m.len()))
+            .sum::<u64>();
+        while total_size > self.max_size && !entries.is_empty() {
+            let oldest = entries.remove(0);
+            let size = fs::metadata(&oldest.path).map_or(0, |m| m.len());
+            eprintln!("[Warning] Spool size limit exceeded, dropping batch {}", oldest.seq);
+            self.remove(&oldest)?;
+            total_size -= size;
+        }
+        Ok(())
+    }
+
+    /// Returns the queued batches oldest first, dropping any that exceeded the age limit.
+    pub fn entries(&self) -> io::Result<Vec<SpoolEntry>> {
+        let cutoff = now().saturating_sub(self.max_age);
+        let mut entries = Vec::new();
+        for entry in self.scan()? {
+            if entry.created < cutoff {
+                eprintln!("[Warning] Spooled batch {} exceeded the age limit, dropping it", entry.seq);
+                self.remove(&entry)?;
+            } else {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    pub fn remove(&self, entry: &SpoolEntry) -> io::Result<()> {
+        match fs::remove_file(&entry.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn scan(&self) -> io::Result<Vec<SpoolEntry>> {
+        let mut entries = Vec::new();
+        for dir_entry in fs::read_dir(&self.dir)? {
+            if let Some(entry) = parse_entry(&dir_entry?.path()) {
+                entries.push(entry);
+            }
+        }
+        entries.sort_by_key(|e| e.seq);
+        Ok(entries)
+    }
+}
+
+fn parse_entry(path: &Path) -> Option<SpoolEntry> {
+    if path.extension()? != EXTENSION {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    let (seq, created) = stem.split_once('-')?;
+    Some(SpoolEntry {
+        seq: seq.parse().ok()?,
+        created: created.parse().ok()?,
+        path: path.to_path_buf(),
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(dir: &Path, max_size: u64, max_age: u64) -> SpoolConfig {
+        SpoolConfig {
+            dir: dir.to_str().unwrap().to_string(),
+            max_size,
+            max_age,
+        }
+    }
+
+    #[test]
+    fn test_push_and_reopen() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(&config(temp_dir.path(), 1024, 3600)).unwrap();
+        spool.push("a 1").unwrap();
+        spool.push("b 2").unwrap();
+
+        let entries = spool.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].read().unwrap(), "a 1");
+        assert_eq!(entries[1].read().unwrap(), "b 2");
+        spool.remove(&entries[0]).unwrap();
+
+        // A new spool over the same directory keeps the queue and continues the sequence.
+        let mut spool = Spool::open(&config(temp_dir.path(), 1024, 3600)).unwrap();
+        spool.push("c 3").unwrap();
+        let batches: Vec<String> = spool.entries().unwrap().iter().map(|e| e.read().unwrap()).collect();
+        assert_eq!(batches, vec!["b 2", "c 3"]);
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut spool = Spool::open(&config(temp_dir.path(), 10, 3600)).unwrap();
+        spool.push("aaaa").unwrap();
+        spool.push("bbbb").unwrap();
+        spool.push("cccc").unwrap();
+
+        let batches: Vec<String> = spool.entries().unwrap().iter().map(|e| e.read().unwrap()).collect();
+        assert_eq!(batches, vec!["bbbb", "cccc"]);
+    }
+
+    #[test]
+    fn test_age_limit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::write(temp_dir.path().join(format!("{:020}-{}.lp", 0, now() - 7200)), "old 1").unwrap();
+        let mut spool = Spool::open(&config(temp_dir.path(), 1024, 3600)).unwrap();
+        spool.push("new 2").unwrap();
+
+        let entries = spool.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].seq, 1);
+        assert_eq!(entries[0].read().unwrap(), "new 2");
+    }
+}
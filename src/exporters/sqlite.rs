@@ -0,0 +1,258 @@
+use super::{ExportError, Exporter};
+use crate::collectors::{Metric, Value};
+use crate::config::SqliteConfig;
+use async_trait::async_trait;
+use rusqlite::{Connection, OptionalExtension, Transaction, params};
+use serde_json::{Map, json};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS series (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        measurement TEXT NOT NULL,
+        field TEXT NOT NULL,
+        tags TEXT NOT NULL,
+        UNIQUE (measurement, field, tags)
+    );
+    CREATE TABLE IF NOT EXISTS samples (
+        series_id INTEGER NOT NULL REFERENCES series (id),
+        timestamp INTEGER NOT NULL,
+        value REAL,
+        value_text TEXT
+    );
+    CREATE INDEX IF NOT EXISTS samples_series_timestamp ON samples (series_id, timestamp);
+    CREATE INDEX IF NOT EXISTS samples_timestamp ON samples (timestamp);
+";
+
+// Expired samples are deleted at most this often.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Appends every collection cycle to a local SQLite database.
+///
+/// Each distinct measurement, field and tag set is stored once in `series`, with the tags as a JSON object with
+/// sorted keys. `samples` holds one row per metric, with the timestamp in milliseconds since the
+/// Unix epoch. String values go to `value_text`, all others to `value`.
+pub struct SqliteExporter {
+    config: SqliteConfig,
+    connection: Connection,
+    series: HashMap<(String, String, String), i64>,
+    last_prune: Option<SystemTime>,
+}
+
+impl SqliteExporter {
+    pub fn open(config: &SqliteConfig) -> rusqlite::Result<Self> {
+        let connection = Connection::open(&config.path)?;
+        // WAL lets other processes query the database while the agent writes to it.
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(SqliteExporter {
+            config: config.clone(),
+            connection,
+            series: HashMap::new(),
+            last_prune: None,
+        })
+    }
+}
+
+#[async_trait]
+impl Exporter for SqliteExporter {
+    fn name(&self) -> &str {
+        "sqlite"
+    }
+
+    async fn export(&mut self, metrics: &[Metric], _deadline: Instant) -> Result<(), ExportError> {
+        let now = SystemTime::now();
+        let transaction = self.connection.transaction()?;
+        // Series created in this transaction are cached only once it commits, a rollback would
+        // leave ids in the cache that do not exist in the database.
+        let mut created = HashMap::new();
+        for metric in metrics {
+            let tags = format_tags(&metric.tags);
+            // Keyed on measurement and field, different pairs can have the same flat name.
+            let key = (metric.measurement.clone(), metric.field.clone(), tags);
+            let series_id = match self.series.get(&key).or_else(|| created.get(&key)) {
+                Some(id) => *id,
+                None => {
+                    let id = series_id(&transaction, metric, &key.2)?;
+                    created.insert(key, id);
+                    id
+                }
+            };
+
+            let (value, value_text) = match &metric.value {
+                Value::String(v) => (None, Some(v.as_str())),
+                value => (value.as_f64(), None),
+            };
+            transaction
+                .prepare_cached("INSERT INTO samples (series_id, timestamp, value, value_text) VALUES (?1, ?2, ?3, ?4)")?
+                .execute(params![series_id, unix_millis(metric.timestamp.unwrap_or(now)), value, value_text])?;
+        }
+
+        let prune = self.last_prune.is_none_or(|last| now.duration_since(last).unwrap_or_default() >= PRUNE_INTERVAL);
+        // A retention reaching back further than the clock can represent keeps everything.
+        if prune && let Some(cutoff) = now.checked_sub(Duration::from_secs(self.config.retention)) {
+            transaction.execute("DELETE FROM samples WHERE timestamp < ?1", params![unix_millis(cutoff)])?;
+        }
+        transaction.commit()?;
+
+        self.series.extend(created);
+        if prune {
+            self.last_prune = Some(now);
+        }
+        Ok(())
+    }
+}
+
+fn unix_millis(timestamp: SystemTime) -> i64 {
+    timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64
+}
+
+/// Formats tags as a JSON object. Keys are sorted, so the same tag set always yields the same text.
+pub(crate) fn format_tags(tags: &[(String, String)]) -> String {
+    let tags: Map<String, serde_json::Value> = tags.iter().map(|(k, v)| (k.clone(), json!(v))).collect();
+    serde_json::Value::Object(tags).to_string()
+}
+
+/// Returns the id of a series, creating the series if it does not exist yet.
+fn series_id(transaction: &Transaction, metric: &Metric, tags: &str) -> rusqlite::Result<i64> {
+    let existing = transaction
+        .query_row(
+            "SELECT id FROM series WHERE measurement = ?1 AND field = ?2 AND tags = ?3",
+            params![metric.measurement, metric.field, tags],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(id) = existing {
+        return Ok(id);
+    }
+    transaction.execute(
+        "INSERT INTO series (name, measurement, field, tags) VALUES (?1, ?2, ?3, ?4)",
+        params![metric.name(), metric.measurement, metric.field, tags],
+    )?;
+    Ok(transaction.last_insert_rowid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporters::testutil::deadline;
+
+    fn stamped(mut metric: Metric, secs: u64) -> Metric {
+        metric.timestamp = Some(UNIX_EPOCH + Duration::from_secs(secs));
+        metric
+    }
+
+    fn cpu(core: &str, value: f64) -> Metric {
+        Metric::new("cpu", "usage", Value::Float(value), vec![("core".to_string(), core.to_string())])
+    }
+
+    #[test]
+    fn test_format_tags() {
+        let tags = vec![("mount".to_string(), "/".to_string()), ("device".to_string(), "sda".to_string())];
+        assert_eq!(format_tags(&tags), r#"{"device":"sda","mount":"/"}"#);
+        assert_eq!(format_tags(&[]), "{}");
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SqliteConfig {
+            path: dir.path().join("metrics.db").to_string_lossy().into_owned(),
+            retention: 7 * 24 * 60 * 60,
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+        let mut exporter = SqliteExporter::open(&config).unwrap();
+        let metrics = [
+            stamped(cpu("cpu0", 0.5), now),
+            stamped(cpu("cpu1", 1.5), now),
+            stamped(Metric::new("disk", "model", Value::String("Disk A".to_string()), vec![]), now),
+        ];
+        exporter.export(&metrics, deadline()).await.unwrap();
+        exporter.export(&[stamped(cpu("cpu0", 2.5), now + 15)], deadline()).await.unwrap();
+
+        // A reopened database reuses the existing series.
+        let mut exporter = SqliteExporter::open(&config).unwrap();
+        exporter.export(&[stamped(cpu("cpu0", 3.5), now + 30)], deadline()).await.unwrap();
+
+        // `disk_io_time` from a different measurement and field is a series of its own.
+        let metrics = [
+            stamped(Metric::new("disk", "io_time", Value::UInt(1), vec![]), now + 30),
+            stamped(Metric::new("disk_io", "time", Value::UInt(2), vec![]), now + 30),
+        ];
+        exporter.export(&metrics, deadline()).await.unwrap();
+
+        let series: i64 = exporter.connection.query_row("SELECT COUNT(*) FROM series", [], |row| row.get(0)).unwrap();
+        assert_eq!(series, 5);
+        let values: Vec<f64> = exporter
+            .connection
+            .prepare(
+                "SELECT value FROM samples JOIN series ON series.id = samples.series_id
+                 WHERE name = 'cpu_usage' AND json_extract(tags, '$.core') = 'cpu0' ORDER BY timestamp",
+            )
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(values, vec![0.5, 2.5, 3.5]);
+        let model: String = exporter
+            .connection
+            .query_row("SELECT value_text FROM samples WHERE value IS NULL", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(model, "Disk A");
+    }
+
+    #[tokio::test]
+    async fn test_failed_export_does_not_cache_series() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SqliteConfig {
+            path: dir.path().join("metrics.db").to_string_lossy().into_owned(),
+            retention: 7 * 24 * 60 * 60,
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+        // The series is inserted, then the sample insert fails and the transaction rolls back.
+        let mut exporter = SqliteExporter::open(&config).unwrap();
+        exporter.connection.execute_batch("DROP TABLE samples").unwrap();
+        assert!(exporter.export(&[stamped(cpu("cpu0", 0.5), now)], deadline()).await.is_err());
+        assert!(exporter.series.is_empty());
+
+        exporter.connection.execute_batch(SCHEMA).unwrap();
+        exporter.export(&[stamped(cpu("cpu0", 1.5), now)], deadline()).await.unwrap();
+        let samples: i64 = exporter
+            .connection
+            .query_row("SELECT COUNT(*) FROM samples JOIN series ON series.id = samples.series_id", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(samples, 1);
+    }
+
+    #[tokio::test]
+    async fn test_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SqliteConfig {
+            path: dir.path().join("metrics.db").to_string_lossy().into_owned(),
+            retention: 60,
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+        let mut exporter = SqliteExporter::open(&config).unwrap();
+        exporter.export(&[stamped(cpu("cpu0", 0.5), now - 120), stamped(cpu("cpu0", 1.5), now)], deadline()).await.unwrap();
+
+        let samples: i64 = exporter.connection.query_row("SELECT COUNT(*) FROM samples", [], |row| row.get(0)).unwrap();
+        assert_eq!(samples, 1);
+
+        // A retention longer than the clock can go back keeps everything.
+        let config = SqliteConfig {
+            retention: u64::MAX,
+            ..config
+        };
+        let mut exporter = SqliteExporter::open(&config).unwrap();
+        exporter.export(&[stamped(cpu("cpu0", 0.5), now - 120)], deadline()).await.unwrap();
+        let samples: i64 = exporter.connection.query_row("SELECT COUNT(*) FROM samples", [], |row| row.get(0)).unwrap();
+        assert_eq!(samples, 2);
+    }
+}
@@ -0,0 +1,205 @@
+use super::{ExportError, Exporter};
+use crate::collectors::{Metric, Value};
+use crate::config::StatsdConfig;
+use async_trait::async_trait;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::net::{UdpSocket, lookup_host};
+use tokio::time::Instant;
+
+/// Sends metrics as StatsD gauges over UDP, optionally with DogStatsD tags.
+pub struct StatsdExporter {
+    config: StatsdConfig,
+    hostname: String,
+    socket: UdpSocket,
+}
+
+impl StatsdExporter {
+    /// Resolves the agent address and opens a UDP socket connected to it.
+    pub async fn connect(config: &StatsdConfig, hostname: &str) -> io::Result<Self> {
+        Ok(StatsdExporter {
+            config: config.clone(),
+            hostname: hostname.to_string(),
+            socket: connect_udp(&config.address).await?,
+        })
+    }
+}
+
+#[async_trait]
+impl Exporter for StatsdExporter {
+    fn name(&self) -> &str {
+        "statsd"
+    }
+
+    async fn export(&mut self, metrics: &[Metric], _deadline: Instant) -> Result<(), ExportError> {
+        let lines = format_metrics(metrics, &self.hostname, &self.config);
+        for packet in pack(&lines, self.config.mtu) {
+            self.socket.send(packet.as_bytes()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves `address` and opens a UDP socket of the matching family connected to it.
+pub(crate) async fn connect_udp(address: &str) -> io::Result<UdpSocket> {
+    let addr = lookup_host(address)
+        .await?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("cannot resolve {}", address)))?;
+    let local: SocketAddr = match addr {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(addr).await?;
+    Ok(socket)
+}
+
+/// Replaces characters that separate the parts of a StatsD line.
+fn sanitize(value: &str, extra: &[char]) -> String {
+    value
+        .chars()
+        .map(|c| if c == ':' || c == '|' || c == '@' || c.is_whitespace() || extra.contains(&c) { '_' } else { c })
+        .collect()
+}
+
+fn format_value(value: &Value) -> Option<String> {
+    match value {
+        Value::Int(v) => Some(v.to_string()),
+        Value::UInt(v) => Some(v.to_string()),
+        Value::Float(v) if v.is_finite() => Some(v.to_string()),
+        Value::Bool(v) => Some((*v as u8).to_string()),
+        Value::Float(_) | Value::String(_) => None,
+    }
+}
+
+/// Formats each metric as a gauge line. String and non-finite values are skipped.
+///
+/// With DogStatsD enabled, the tags and the hostname are sent as `#k:v` tags. Plain StatsD has
+/// no tags, so the tag values are appended to the metric name instead to keep the series apart.
+fn format_metrics(metrics: &[Metric], hostname: &str, config: &StatsdConfig) -> Vec<String> {
+    let mut lines = Vec::new();
+    for metric in metrics {
+        let Some(value) = format_value(&metric.value) else {
+            continue;
+        };
+
+        let mut name = match &config.prefix {
+            Some(prefix) => format!("{}.{}", prefix, metric.name()),
+            None => metric.name(),
+        };
+        let mut suffix = String::new();
+        if config.dogstatsd {
+            let tags = metric
+                .tags
+                .iter()
+                .filter(|(k, _)| k != "host")
+                .map(|(k, v)| (k.as_str(), v.as_str()))
+                .chain([("host", hostname)])
+                .map(|(k, v)| format!("{}:{}", sanitize(k, &[',', '#']), sanitize(v, &[',', '#'])))
+                .collect::<Vec<_>>();
+            suffix = format!("|#{}", tags.join(","));
+        } else {
+            for (_, v) in &metric.tags {
+                name.push('.');
+                name.push_str(&sanitize(v, &['.']));
+            }
+        }
+        let name = sanitize(&name, &[]);
+
+        // A signed gauge value is a relative change in StatsD, so a negative value is sent as a reset to 0 first.
+        if value.starts_with('-') {
+            lines.push(format!("{}:0|g{}\n{}:{}|g{}", name, suffix, name, value, suffix));
+        } else {
+            lines.push(format!("{}:{}|g{}", name, value, suffix));
+        }
+    }
+    lines
+}
+
+/// Packs newline separated lines into packets of at most `mtu` bytes.
+///
+/// A single line longer than `mtu` is sent in a packet of its own.
+fn pack(lines: &[String], mtu: usize) -> Vec<String> {
+    let mut packets = Vec::new();
+    let mut packet = String::new();
+    for line in lines {
+        if !packet.is_empty() && packet.len() + 1 + line.len() > mtu {
+            packets.push(std::mem::take(&mut packet));
+        }
+        if !packet.is_empty() {
+            packet.push('\n');
+        }
+        packet.push_str(line);
+    }
+    if !packet.is_empty() {
+        packets.push(packet);
+    }
+    packets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporters::testutil::deadline;
+
+    fn statsd_config(address: String, dogstatsd: bool, mtu: usize) -> StatsdConfig {
+        StatsdConfig {
+            address,
+            prefix: None,
+            dogstatsd,
+            mtu,
+        }
+    }
+
+    fn test_metrics() -> Vec<Metric> {
+        vec![
+            Metric::new("cpu", "usage", Value::Float(0.5), vec![("core".to_string(), "cpu0".to_string())]),
+            Metric::new("disk", "model", Value::String("Disk A".to_string()), vec![]),
+            Metric::new("system", "offset", Value::Int(-3), vec![]),
+            Metric::new("memory", "total", Value::UInt(1024), vec![]),
+            Metric::new("disk", "read_bytes", Value::UInt(u64::MAX), vec![]),
+        ]
+    }
+
+    #[test]
+    fn test_format_metrics() {
+        let config = statsd_config(String::new(), false, 1432);
+        assert_eq!(
+            format_metrics(&test_metrics(), "test-host", &config),
+            vec!["cpu_usage.cpu0:0.5|g", "system_offset:0|g\nsystem_offset:-3|g", "memory_total:1024|g", "disk_read_bytes:18446744073709551615|g"]
+        );
+    }
+
+    #[test]
+    fn test_format_metrics_dogstatsd() {
+        let mut config = statsd_config(String::new(), true, 1432);
+        config.prefix = Some("rsys".to_string());
+        let metrics = vec![Metric::new("gpu", "usage", Value::Float(1.0), vec![("card".to_string(), "a,b|c".to_string())])];
+        assert_eq!(format_metrics(&metrics, "test-host", &config), vec!["rsys.gpu_usage:1|g|#card:a_b_c,host:test-host"]);
+    }
+
+    #[test]
+    fn test_pack() {
+        let lines = vec!["a:1|g".to_string(), "b:2|g".to_string(), "a_very_long_name:3|g".to_string()];
+        assert_eq!(pack(&lines, 11), vec!["a:1|g\nb:2|g", "a_very_long_name:3|g"]);
+        assert_eq!(pack(&lines, 10), vec!["a:1|g", "b:2|g", "a_very_long_name:3|g"]);
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let config = statsd_config(server.local_addr().unwrap().to_string(), true, 64);
+
+        let mut exporter = StatsdExporter::connect(&config, "test-host").await.unwrap();
+        exporter.export(&test_metrics(), deadline()).await.unwrap();
+
+        let mut buf = [0u8; 1500];
+        let n = server.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"cpu_usage:0.5|g|#core:cpu0,host:test-host");
+        let n = server.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"system_offset:0|g|#host:test-host\nsystem_offset:-3|g|#host:test-host");
+        let n = server.recv(&mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"memory_total:1024|g|#host:test-host");
+    }
+}
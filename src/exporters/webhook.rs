@@ -0,0 +1,222 @@
+use super::jsonl::{json_metric, json_value, unix_millis};
+use super::retry::with_retry;
+use super::{ExportError, Exporter};
+use crate::collectors::{Metric, Value};
+use crate::config::WebhookConfig;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
+use std::io;
+use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
+
+const DEFAULT_TEMPLATE: &str = r#"{"host": {{host}}, "timestamp": {{timestamp}}, "metrics": {{metrics}}}"#;
+
+/// POSTs each collection cycle as a JSON document rendered from a user-supplied template.
+pub struct WebhookExporter {
+    client: Client,
+    config: WebhookConfig,
+    hostname: String,
+}
+
+impl WebhookExporter {
+    /// Fails if the templates use an unknown placeholder or do not render to valid JSON, which is
+    /// checked by rendering a sample metric, so a broken template is reported at startup.
+    pub fn new(client: &Client, config: &WebhookConfig, hostname: &str) -> io::Result<Self> {
+        let sample = Metric::new("sample", "value", Value::Float(0.0), vec![]);
+        render_body(&[sample], hostname, config).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(WebhookExporter {
+            client: client.clone(),
+            config: config.clone(),
+            hostname: hostname.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl Exporter for WebhookExporter {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn export(&mut self, metrics: &[Metric], deadline: Instant) -> Result<(), ExportError> {
+        if metrics.is_empty() {
+            return Ok(());
+        }
+        let body = render_body(metrics, &self.hostname, &self.config)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        with_retry(&self.config.retry, deadline, || async {
+            let mut request_builder = self
+                .client
+                .post(&self.config.url)
+                .timeout(Duration::from_secs(self.config.timeout))
+                .header("Content-Type", "application/json");
+            if let Some(token) = &self.config.token {
+                request_builder = request_builder.bearer_auth(token);
+            } else if let Some(username) = &self.config.username {
+                request_builder = request_builder.basic_auth(username, self.config.password.as_ref());
+            }
+            for (name, value) in &self.config.headers {
+                request_builder = request_builder.header(name, value);
+            }
+            request_builder.body(body.clone()).send().await?.error_for_status()
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+/// Replaces every `{{ key }}` in `template` with the JSON returned by `lookup`.
+fn render(template: &str, lookup: impl Fn(&str) -> Option<serde_json::Value>) -> Result<String, String> {
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| "unclosed placeholder in webhook template".to_string())?;
+        let key = rest[start + 2..start + end].trim();
+        let value = lookup(key).ok_or_else(|| format!("unknown placeholder {{{{{}}}}} in webhook template", key))?;
+        output.push_str(&value.to_string());
+        rest = &rest[start + end + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Renders the request body.
+///
+/// The body template may use `{{host}}`, `{{timestamp}}` (milliseconds since the Unix epoch) and
+/// `{{metrics}}`, the array of all metrics. Each metric is rendered from the metric template, which
+/// may use `{{host}}`, `{{timestamp}}`, `{{name}}`, `{{measurement}}`, `{{field}}`, `{{value}}`,
+/// `{{tags}}` and `{{tag.KEY}}`. Without a metric template, metrics are rendered as in the JSON Lines
+/// exporter. Placeholders expand to JSON, so strings are quoted, and the result must be valid JSON.
+fn render_body(metrics: &[Metric], hostname: &str, config: &WebhookConfig) -> Result<String, String> {
+    let now = SystemTime::now();
+    let mut rendered = Vec::new();
+    for metric in metrics {
+        let record = json_metric(metric, now);
+        let value = match &config.metric_template {
+            Some(template) => {
+                let rendered = render(template, |key| match key {
+                    "host" => Some(json!(hostname)),
+                    "name" => Some(json!(metric.name())),
+                    "timestamp" | "measurement" | "field" | "tags" => Some(record[key].clone()),
+                    "value" => Some(json_value(&metric.value)),
+                    _ => key.strip_prefix("tag.").map(|tag| {
+                        metric
+                            .tags
+                            .iter()
+                            .find(|(k, _)| k == tag)
+                            .map_or(serde_json::Value::Null, |(_, v)| json!(v))
+                    }),
+                })?;
+                serde_json::from_str(&rendered).map_err(|e| format!("webhook metric template is not valid JSON: {}", e))?
+            }
+            None => record,
+        };
+        rendered.push(value);
+    }
+
+    let body = render(config.template.as_deref().unwrap_or(DEFAULT_TEMPLATE), |key| match key {
+        "host" => Some(json!(hostname)),
+        "timestamp" => Some(json!(unix_millis(now))),
+        "metrics" => Some(serde_json::Value::Array(rendered.clone())),
+        _ => None,
+    })?;
+    serde_json::from_str::<serde_json::Value>(&body).map_err(|e| format!("webhook template is not valid JSON: {}", e))?;
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RetryConfig;
+    use crate::exporters::testutil::{deadline, http_server};
+    use std::collections::BTreeMap;
+    use std::time::UNIX_EPOCH;
+
+    fn webhook_config(url: String) -> WebhookConfig {
+        WebhookConfig {
+            url,
+            template: None,
+            metric_template: None,
+            headers: BTreeMap::new(),
+            token: None,
+            username: None,
+            password: None,
+            timeout: 10,
+            retry: RetryConfig::default(),
+        }
+    }
+
+    fn test_metrics() -> Vec<Metric> {
+        let mut metric = Metric::new("cpu", "usage", Value::Float(0.5), vec![("core".to_string(), "cpu\"0".to_string())]);
+        metric.timestamp = Some(UNIX_EPOCH + Duration::from_millis(1678886400123));
+        vec![metric]
+    }
+
+    #[test]
+    fn test_render_body_templates() {
+        let mut config = webhook_config(String::new());
+        config.template = Some(r#"{"source": "rsysmetrics", "node": {{ host }}, "points": {{metrics}}}"#.to_string());
+        config.metric_template =
+            Some(r#"{"metric": {{name}}, "core": {{tag.core}}, "gpu": {{tag.gpu}}, "v": {{value}}, "ts": {{timestamp}}}"#.to_string());
+
+        let body: serde_json::Value = serde_json::from_str(&render_body(&test_metrics(), "test-host", &config).unwrap()).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "source": "rsysmetrics",
+                "node": "test-host",
+                "points": [{"metric": "cpu_usage", "core": "cpu\"0", "gpu": null, "v": 0.5, "ts": 1678886400123u64}],
+            })
+        );
+    }
+
+    #[test]
+    fn test_render_body_errors() {
+        let mut config = webhook_config(String::new());
+        config.template = Some(r#"{"metrics": {{metric}}}"#.to_string());
+        assert!(render_body(&test_metrics(), "test-host", &config).unwrap_err().contains("unknown placeholder {{metric}}"));
+
+        config.template = Some(r#"{"metrics": {{metrics}}"#.to_string());
+        assert!(render_body(&test_metrics(), "test-host", &config).unwrap_err().contains("not valid JSON"));
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        let mut config = webhook_config(String::new());
+        config.metric_template = Some(r#"{"v": {{value}}, "unit": {{unit}}}"#.to_string());
+        let error = WebhookExporter::new(&Client::new(), &config, "test-host").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(error.to_string().contains("unknown placeholder {{unit}}"));
+
+        config.metric_template = None;
+        config.template = Some(r#"{"metrics": [{{metrics}}"#.to_string());
+        let error = WebhookExporter::new(&Client::new(), &config, "test-host").err().unwrap();
+        assert!(error.to_string().contains("not valid JSON"));
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let (url, requests) = http_server(vec![(202, "")]).await;
+        let mut config = webhook_config(format!("{}/ingest", url));
+        config.token = Some("secret".to_string());
+        config.headers.insert("X-Source".to_string(), "rsysmetrics".to_string());
+
+        let mut exporter = WebhookExporter::new(&Client::new(), &config, "test-host").unwrap();
+        exporter.export(&test_metrics(), deadline()).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].target, "/ingest");
+        assert_eq!(requests[0].header("authorization"), Some("Bearer secret"));
+        assert_eq!(requests[0].header("x-source"), Some("rsysmetrics"));
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(body["host"], json!("test-host"));
+        assert_eq!(body["metrics"][0]["measurement"], json!("cpu"));
+    }
+}
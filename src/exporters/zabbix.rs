@@ -0,0 +1,286 @@
+use super::{ExportError, Exporter};
+use crate::collectors::{Metric, Value};
+use crate::config::ZabbixConfig;
+use async_trait::async_trait;
+use serde_json::json;
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{self, Instant};
+
+// zabbix_sender sends at most this many values per request.
+const MAX_BATCH_ITEMS: usize = 250;
+
+// Responses are a short JSON object, anything larger is not a Zabbix server.
+const MAX_RESPONSE_SIZE: usize = 64 * 1024;
+
+const FLAG_ZABBIX: u8 = 0x01;
+const FLAG_LARGE: u8 = 0x04;
+
+/// Sends metrics as Zabbix trapper items over the sender protocol.
+///
+/// Item keys are the metric name with the tag values as parameters, e.g. `cpu_usage_user[cpu3]`.
+/// The items must exist on the Zabbix host as "Zabbix trapper" items, values for unknown items are
+/// counted as failed by the server.
+pub struct ZabbixExporter {
+    config: ZabbixConfig,
+    host: String,
+}
+
+impl ZabbixExporter {
+    pub fn new(config: &ZabbixConfig, hostname: &str) -> Self {
+        ZabbixExporter {
+            config: config.clone(),
+            host: config.host.clone().unwrap_or_else(|| hostname.to_string()),
+        }
+    }
+
+    async fn send(&self, request: &[u8]) -> io::Result<Vec<u8>> {
+        let mut stream = TcpStream::connect(&self.config.address).await?;
+        let mut packet = Vec::with_capacity(13 + request.len());
+        packet.extend_from_slice(b"ZBXD");
+        packet.push(FLAG_ZABBIX);
+        packet.extend_from_slice(&(request.len() as u32).to_le_bytes());
+        packet.extend_from_slice(&0u32.to_le_bytes());
+        packet.extend_from_slice(request);
+        stream.write_all(&packet).await?;
+
+        let mut header = [0u8; 5];
+        stream.read_exact(&mut header).await?;
+        if &header[..4] != b"ZBXD" || header[4] & !FLAG_LARGE != FLAG_ZABBIX {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected Zabbix response header"));
+        }
+        // Large packets have 64-bit lengths, followed by the reserved field of the same width.
+        let length = if header[4] & FLAG_LARGE != 0 {
+            let mut length = [0u8; 16];
+            stream.read_exact(&mut length).await?;
+            u64::from_le_bytes(length[..8].try_into().unwrap()) as usize
+        } else {
+            let mut length = [0u8; 8];
+            stream.read_exact(&mut length).await?;
+            u32::from_le_bytes(length[..4].try_into().unwrap()) as usize
+        };
+        if length > MAX_RESPONSE_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Zabbix response is too large"));
+        }
+        let mut response = vec![0u8; length];
+        stream.read_exact(&mut response).await?;
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl Exporter for ZabbixExporter {
+    fn name(&self) -> &str {
+        "zabbix"
+    }
+
+    /// Sends the metrics in batches and fails with the number of items the server did not process,
+    /// along with the server's `info` for the first batch with failed items.
+    async fn export(&mut self, metrics: &[Metric], _deadline: Instant) -> Result<(), ExportError> {
+        let items = format_items(metrics, &self.host);
+        let mut processed = 0;
+        let mut failed = 0;
+        let mut reason = None;
+        for batch in items.chunks(MAX_BATCH_ITEMS) {
+            let request = json!({"request": "sender data", "data": batch}).to_string();
+            let timeout = Duration::from_secs(self.config.timeout);
+            let result = match time::timeout(timeout, self.send(request.as_bytes())).await {
+                Ok(response) => response.and_then(|response| parse_response(&response)),
+                Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "request to Zabbix timed out")),
+            };
+            let (batch_processed, batch_failed, info) = match result {
+                Ok(counts) => counts,
+                Err(e) => {
+                    // The counts of the batches already sent would otherwise be lost with the error.
+                    if processed + failed > 0 {
+                        eprintln!("[Warning] Zabbix processed {} and failed {} items before the error", processed, failed);
+                    }
+                    return Err(e.into());
+                }
+            };
+            processed += batch_processed;
+            failed += batch_failed;
+            if batch_failed > 0 && reason.is_none() {
+                reason = Some(info);
+            }
+        }
+
+        if let Some(reason) = reason {
+            return Err(ExportError::Rejected {
+                failed,
+                total: processed + failed,
+                reason,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Formats an item key from the metric name and tag values.
+///
+/// Parameters that contain a separator, a quote or leading spaces are quoted, as in the Zabbix key
+/// syntax. The `host` tag is left out, the values are sent for the configured host.
+fn item_key(metric: &Metric) -> String {
+    let name: String = metric
+        .name()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' { c } else { '_' })
+        .collect();
+    let parameters: Vec<String> = metric
+        .tags
+        .iter()
+        .filter(|(k, _)| k != "host")
+        .map(|(_, v)| {
+            if v.contains([',', ']', '"']) || v.starts_with(' ') {
+                format!("\"{}\"", v.replace('"', "\\\""))
+            } else {
+                v.clone()
+            }
+        })
+        .collect();
+    if parameters.is_empty() {
+        name
+    } else {
+        format!("{}[{}]", name, parameters.join(","))
+    }
+}
+
+/// Formats the sender data items. Non-finite values are skipped, Zabbix cannot store them.
+fn format_items(metrics: &[Metric], host: &str) -> Vec<serde_json::Value> {
+    let now = SystemTime::now();
+    metrics
+        .iter()
+        .filter_map(|metric| {
+            let value = match &metric.value {
+                Value::Int(v) => v.to_string(),
+                Value::UInt(v) => v.to_string(),
+                Value::Float(v) if v.is_finite() => v.to_string(),
+                Value::Float(_) => return None,
+                Value::Bool(v) => (*v as u8).to_string(),
+                Value::String(v) => v.clone(),
+            };
+            let time = metric.timestamp.unwrap_or(now).duration_since(UNIX_EPOCH).unwrap_or_default();
+            Some(json!({
+                "host": host,
+                "key": item_key(metric),
+                "value": value,
+                "clock": time.as_secs(),
+                "ns": time.subsec_nanos(),
+            }))
+        })
+        .collect()
+}
+
+/// Returns the processed and failed counts and the `info` from a response such as
+/// `{"response": "success", "info": "processed: 3; failed: 1; total: 4; seconds spent: 0.000055"}`.
+fn parse_response(response: &[u8]) -> io::Result<(usize, usize, String)> {
+    let response: serde_json::Value = serde_json::from_slice(response).map_err(io::Error::other)?;
+    let info = response["info"].as_str().unwrap_or_default();
+    if response["response"] != "success" {
+        return Err(io::Error::other(format!("Zabbix rejected the request: {}", info)));
+    }
+    let count = |name: &str| {
+        info.split(';')
+            .filter_map(|part| part.trim().strip_prefix(name))
+            .find_map(|value| value.trim().parse().ok())
+    };
+    match (count("processed:"), count("failed:")) {
+        (Some(processed), Some(failed)) => Ok((processed, failed, info.to_string())),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unexpected Zabbix response: {}", info))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporters::testutil::deadline;
+    use tokio::net::TcpListener;
+
+    fn test_metrics() -> Vec<Metric> {
+        let mut metrics = vec![
+            Metric::new("cpu", "usage_user", Value::Float(0.5), vec![("core".to_string(), "cpu3".to_string())]),
+            Metric::new("disk", "model", Value::String("Disk A".to_string()), vec![]),
+            Metric::new("gpu", "power", Value::Float(f64::NAN), vec![]),
+        ];
+        for metric in &mut metrics {
+            metric.timestamp = Some(UNIX_EPOCH + Duration::from_millis(1678886400123));
+        }
+        metrics
+    }
+
+    #[test]
+    fn test_item_key() {
+        assert_eq!(item_key(&test_metrics()[0]), "cpu_usage_user[cpu3]");
+        assert_eq!(item_key(&test_metrics()[1]), "disk_model");
+        let tags = vec![
+            ("host".to_string(), "other".to_string()),
+            ("mount".to_string(), "/mnt/a,b".to_string()),
+            ("label".to_string(), "say \"hi\"".to_string()),
+        ];
+        let metric = Metric::new("disk", "used space", Value::UInt(1), tags);
+        assert_eq!(item_key(&metric), r#"disk_used_space["/mnt/a,b","say \"hi\""]"#);
+    }
+
+    #[test]
+    fn test_format_items() {
+        let items = format_items(&test_metrics(), "zabbix-host");
+        assert_eq!(
+            items,
+            vec![
+                json!({"host": "zabbix-host", "key": "cpu_usage_user[cpu3]", "value": "0.5", "clock": 1678886400u64, "ns": 123000000}),
+                json!({"host": "zabbix-host", "key": "disk_model", "value": "Disk A", "clock": 1678886400u64, "ns": 123000000}),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let response = br#"{"response":"success","info":"processed: 3; failed: 1; total: 4; seconds spent: 0.000055"}"#;
+        let info = "processed: 3; failed: 1; total: 4; seconds spent: 0.000055".to_string();
+        assert_eq!(parse_response(response).unwrap(), (3, 1, info));
+        assert!(parse_response(br#"{"response":"failed","info":"host not found"}"#).is_err());
+        assert!(parse_response(br#"{"response":"success"}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = ZabbixConfig {
+            address: listener.local_addr().unwrap().to_string(),
+            host: None,
+            timeout: 10,
+        };
+        let server = tokio::spawn(async move {
+            let (mut connection, _) = listener.accept().await.unwrap();
+            let mut header = [0u8; 13];
+            connection.read_exact(&mut header).await.unwrap();
+            assert_eq!(&header[..5], b"ZBXD\x01");
+            let mut request = vec![0u8; u32::from_le_bytes(header[5..9].try_into().unwrap()) as usize];
+            connection.read_exact(&mut request).await.unwrap();
+
+            let response = br#"{"response":"success","info":"processed: 1; failed: 1; total: 2; seconds spent: 0.000055"}"#;
+            let mut packet = b"ZBXD\x01".to_vec();
+            packet.extend_from_slice(&(response.len() as u64).to_le_bytes());
+            packet.extend_from_slice(response);
+            connection.write_all(&packet).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&request).unwrap()
+        });
+
+        let mut exporter = ZabbixExporter::new(&config, "test-host");
+        let error = exporter.export(&test_metrics(), deadline()).await.unwrap_err();
+        match error {
+            ExportError::Rejected { failed, total, reason } => {
+                assert_eq!((failed, total), (1, 2));
+                assert_eq!(reason, "processed: 1; failed: 1; total: 2; seconds spent: 0.000055");
+            }
+            error => panic!("unexpected error: {:?}", error),
+        }
+
+        let request = server.await.unwrap();
+        assert_eq!(request["request"], "sender data");
+        assert_eq!(request["data"][0]["host"], "test-host");
+        assert_eq!(request["data"].as_array().unwrap().len(), 2);
+    }
+}
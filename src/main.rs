@@ -1,8 +1,10 @@
+mod check;
 mod config;
 mod collectors;
 mod exporters;
 
-use crate::config::{Config, Exporter};
+use crate::check::{CheckArgs, Status};
+use crate::config::Config;
 use collectors::cpu::CpuCollector;
 use collectors::memory::MemoryCollector;
 use collectors::disk::DiskCollector;
@@ -10,12 +12,16 @@ use collectors::network::NetworkCollector;
 use collectors::system::SystemCollector;
 use collectors::temperature::TemperatureCollector;
 use collectors::gpu::GpuCollector;
-use collectors::Collector;
+use collectors::{Collector, Metric};
+use exporters::Registry;
 use reqwest::Client;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::fs;
+use std::process;
+use std::time::SystemTime;
 use sysinfo::System;
-use tokio::time::{self, Duration, MissedTickBehavior};
+use tokio::signal;
+use tokio::time::{self, Duration, Interval, MissedTickBehavior};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -27,6 +33,53 @@ struct Cli {
     /// Run in oneshot mode
     #[arg(long)]
     oneshot: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the collectors once and check metrics against thresholds, as a Nagios/Icinga plugin
+    Check(CheckArgs),
+}
+
+/// Completes on Ctrl-C, or on SIGTERM as sent by systemd when stopping the service.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler");
+        tokio::select! {
+            _ = signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = signal::ctrl_c().await;
+    }
+}
+
+fn load_config(path: &str) -> Result<Config, String> {
+    let config_str = fs::read_to_string(path).map_err(|e| format!("Failed to read config file: {}", e))?;
+    toml::from_str(&config_str).map_err(|e| format!("Failed to parse config file: {}", e))
+}
+
+/// Collects twice, one interval apart, and returns the second sample, so that rate-based
+/// metrics such as `cpu_usage_iowait` cover a full interval.
+async fn collect_twice(collectors: &mut [Box<dyn Collector>], interval: &mut Interval) -> Vec<Metric> {
+    let mut metrics = Vec::new();
+    for i in 0..2 {
+        metrics.clear();
+        for collector in collectors.iter_mut() {
+            metrics.extend(collector.collect().await);
+        }
+        if i == 0 {
+            interval.tick().await;
+        }
+    }
+    metrics
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -34,14 +87,18 @@ async fn main() {
     let cli = Cli::parse();
 
     // Load configuration
-    let config_str = fs::read_to_string(cli.config).expect("Failed to read config file");
-    let config: Config = toml::from_str(&config_str).expect("Failed to parse config file");
-
-    // Get hostname
-    let hostname = System::host_name().unwrap_or_else(|| {
-        eprintln!("Error: Could not determine hostname.");
-        std::process::exit(1);
-    });
+    let config = match load_config(&cli.config) {
+        Ok(config) => config,
+        // A plugin must report its own failures as UNKNOWN.
+        Err(e) if cli.command.is_some() => {
+            println!("RSYSMETRICS UNKNOWN - {}", e);
+            process::exit(Status::Unknown as i32);
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
 
     // Create collectors
     let mut collectors: Vec<Box<dyn Collector>> = Vec::new();
@@ -72,49 +129,74 @@ async fn main() {
     // Create HTTP client
     let client = Client::new();
 
-    // Start the collection loop
-    println!("Starting metrics collection...");
+    if let Some(Command::Check(args)) = &cli.command {
+        // A plugin should return quickly, so the samples are `--interval` apart rather than a
+        // full collection interval.
+        let mut interval = time::interval(Duration::from_secs(args.interval));
+        interval.tick().await;
+        let metrics = collect_twice(&mut collectors, &mut interval).await;
+        let (status, output) = check::evaluate(&metrics, &args.metrics);
+        println!("{}", output);
+        process::exit(status as i32);
+    }
+
     let mut interval = time::interval(Duration::from_secs(config.collect_interval));
     interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
     // The first tick completes immediately, let's consume it
     interval.tick().await;
 
+    // Start the collection loop. Status messages go to stderr, stdout is reserved for metrics.
+    eprintln!("Starting metrics collection...");
     if cli.oneshot {
-        println!("Running in oneshot mode for testing. Metrics will be printed to the console.");
-        let mut metrics = Vec::new();
-        for _i in 0..2 {
-            metrics.clear();
-            for collector in &mut collectors {
-                metrics.extend(collector.collect().await);
-            }
-            if _i == 0 {
-                interval.tick().await;
-            }
-        }
+        eprintln!("Running in oneshot mode for testing. Metrics will be printed to the console.");
+        let metrics = collect_twice(&mut collectors, &mut interval).await;
         println!("Collected metrics: {:#?}", metrics);
-        println!("\nOneshot mode finished.");
+        eprintln!("\nOneshot mode finished.");
     } else {
-        println!("Running in continuous mode. Metrics will be exported to InfluxDB.");
+        // Get hostname
+        let hostname = System::host_name().unwrap_or_else(|| {
+            eprintln!("Error: Could not determine hostname.");
+            process::exit(1);
+        });
+
+        let mut registry = match Registry::from_config(config.exporters(), &client, &hostname).await {
+            Ok(registry) => registry,
+            Err(e) => {
+                eprintln!("Error: Failed to create exporter: {}", e);
+                process::exit(1);
+            }
+        };
+        if registry.is_empty() {
+            eprintln!("Warning: No exporters configured. Collected metrics will be discarded.");
+        }
+
+        eprintln!("Running in continuous mode. Metrics will be exported.");
+        let export_timeout = Duration::from_secs(config.collect_interval);
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
         loop {
-            interval.tick().await;
-            let now = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = &mut shutdown => break,
+            }
             let mut metrics = Vec::new();
             for collector in &mut collectors {
-                metrics.extend(collector.collect().await);
-            }
-
-            match &config.exporter {
-                Exporter::InfluxDB(influx_config) => {
-                    let lines = exporters::influxdb::format_metrics(&metrics, &hostname, now);
-                    if let Err(e) = exporters::influxdb::export_metrics(&client, influx_config, &lines).await {
-                        eprintln!("[Error] Failed to export metrics: {:#?}", e);
-                    }
+                // Stamp each collector's metrics with its own start time, so a slow collector
+                // does not shift the timestamps of the ones after it.
+                let sampled = SystemTime::now();
+                let mut collected = collector.collect().await;
+                for metric in &mut collected {
+                    metric.timestamp.get_or_insert(sampled);
                 }
+                metrics.extend(collected);
             }
+
+            // Each export is bounded by the collection interval to keep the loop on schedule.
+            registry.export(&metrics, export_timeout).await;
         }
+
+        eprintln!("Shutting down, flushing exporters.");
+        registry.flush().await;
     }
 }
@@ -0,0 +1,42 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// With the JSON Lines exporter writing to stdout, stdout must carry nothing but JSON objects.
+#[test]
+fn test_jsonl_stdout_is_only_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("rsysmetrics.toml");
+    fs::write(
+        &config,
+        "collect_interval = 1\n\
+         [[exporters]]\n[exporters.jsonl]\n\
+         [collectors]\ncpu = false\nmemory = true\nnetwork = false\ndisk = false\nsystem = false\ngpu = false\n",
+    )
+    .unwrap();
+
+    let child = Command::new(env!("CARGO_BIN_EXE_rsysmetrics"))
+        .arg("--config")
+        .arg(&config)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(2500));
+    let status = Command::new("kill").arg("-INT").arg(child.id().to_string()).status().unwrap();
+    assert!(status.success());
+    let output = child.wait_with_output().unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.is_empty());
+    for line in stdout.lines() {
+        let record: serde_json::Value = serde_json::from_str(line).unwrap_or_else(|e| panic!("{:?}: {}", line, e));
+        assert!(record.is_object());
+    }
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Starting metrics collection"));
+    assert!(stderr.contains("Shutting down"));
+}